@@ -0,0 +1,26 @@
+// Record which candle the binary was built against for run manifests, the locked version along with where it
+// came from so a git checkout includes its commit.
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let candle = lock
+        .split("[[package]]")
+        .find(|package| package.contains("name = \"candle-core\""))
+        .map(|package| {
+            let field = |name: &str| {
+                package
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(|value| value.trim_matches(|c| c == ' ' || c == '=' || c == '"').to_string())
+            };
+            match (field("version"), field("source")) {
+                (Some(version), Some(source)) => format!("{} {}", version, source),
+                (Some(version), None) => version,
+                _ => "unknown".to_string(),
+            }
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo:rustc-env=CANDLE_VERSION={}", candle);
+}
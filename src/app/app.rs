@@ -1,156 +1,736 @@
 use crate::{
     app::{
-        message::{self, AppMessage, EventMessage, LossType, ModelCommandMessage, ModelResultMessage},
-        options::{self, Options},
+        device,
+        keys::Action,
+        message::{
+            self, AppMessage, EventMessage, GenerationStep, GenerationSummary, Histogram, Layer, LossType, ModelCommandMessage,
+            ModelResultMessage, PositionLoss, ScoreStep,
+        },
+        options::Options,
     },
+    data::loss_log::{self, LossStream},
     error::VibeError,
+    logger::{Level, Logger},
     model,
-    ui::main_screen,
+    ui::main_screen::{self, LossChart, MainView},
 };
 
-use crossterm::event::{self, KeyCode};
+use crossterm::event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, MouseButton, MouseEventKind};
 use ratatui::{
     DefaultTerminal, Terminal,
     backend::CrosstermBackend,
     crossterm::execute,
     crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
+    layout::{Position, Rect},
 };
+use std::collections::VecDeque;
 use std::io;
-use std::sync::mpsc::{Receiver, Sender};
+use std::iter;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+    mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError},
+};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// How long to wait for the model thread to finish its current work after a shutdown.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
+// How long streaming waits after a batch before asking for the next one, and how many words each batch has.
+const STREAM_INTERVAL: Duration = Duration::from_millis(150);
+const STREAM_BATCH: usize = 1;
+
+// The most generated strings kept while streaming, older ones scroll off.
+const STREAM_LINES: usize = 500;
+
+// How many training progress messages the throughput is averaged over.
+const THROUGHPUT_WINDOW: usize = 20;
+
+// A training loss is a spike when it's this many standard deviations above the mean of the losses reported just
+// before it. The deviation is never taken as less than a share of the mean, so noise on a flat run isn't a spike.
+const SPIKE_WINDOW: usize = 20;
+const SPIKE_SIGMA: f64 = 3.;
+const SPIKE_MIN_DEVIATION: f64 = 0.02;
+
+// Repeats of the same key within this long of each other are dropped.
+const KEY_COOLDOWN: Duration = Duration::from_millis(50);
+
+// How much the smoothing window grows or shrinks per key press, keeping it odd so it stays centered, and the
+// largest it can get.
+const SMOOTHING_STEP: usize = 2;
+const MAX_SMOOTHING: usize = 201;
+
+// How often the screen is redrawn to animate the spinner while the models initialize.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
 
 pub struct App {
     pub terminal: DefaultTerminal,
     pub state: State,
+    pub train_target: usize,
+    pub progress_times: VecDeque<(Instant, usize)>,
+    // The batch size the main run trains with, which the model clamps to the data when asked to.
+    pub batch_size: usize,
     pub show_generated: bool,
+    pub grid_generated: bool,
+    pub show_info: bool,
+    pub show_score: bool,
+    pub show_positions: bool,
+    pub show_probe: bool,
+    pub show_histograms: bool,
+    pub show_floor: bool,
+    // How many of the newest generated strings are scrolled past.
+    pub generated_scroll: usize,
+    // Points the charted training loss is averaged over.
+    pub smoothing: usize,
+    // Where each clickable keybinding was last drawn.
+    pub click_targets: Vec<(Rect, Action)>,
     pub options: Options,
     pub loss_data: Vec<(f64, f64)>,
     pub validation_loss_data: Vec<(f64, f64)>,
+    pub evaluation_loss_data: Vec<(f64, f64)>,
+    pub baseline_loss_data: Vec<(f64, f64)>,
+    pub compare_loss_data: Vec<(f64, f64)>,
+    pub compare_validation_loss_data: Vec<(f64, f64)>,
+    pub loss_stream: Option<LossStream>,
     pub generated_data: Vec<String>,
-    pub model_commands: Sender<ModelCommandMessage>,
+    // The probability of each character of the generated strings, NaN for the tags.
+    pub generated_confidence: Vec<Vec<f32>>,
+    pub latest_batch: Vec<String>,
+    pub pinned_batch: Option<Vec<String>>,
+    pub show_diff: bool,
+    pub generation_summary: Option<GenerationSummary>,
+    pub generation_step: Option<GenerationStep>,
+    pub stream_next: Option<Instant>,
+    pub description: Option<(String, Vec<Layer>)>,
+    pub score: Option<(String, Vec<ScoreStep>)>,
+    pub position_losses: Option<Vec<PositionLoss>>,
+    pub probe: Option<(String, Vec<(String, f32)>)>,
+    // The iteration of the latest weight and gradient histograms along with them.
+    pub histograms: Option<(usize, Vec<Histogram>, Vec<Histogram>)>,
+    pub loss_floor: Option<f32>,
+    // The latest word sampled while training and the iteration it was sampled at.
+    pub live_sample: Option<(usize, String)>,
+    // The latest fraction of saturated hidden activations and the iteration it was measured at.
+    pub saturation: Option<(usize, f32)>,
+    // The iteration the model resumed from, its autosave.
+    pub resumed: Option<usize>,
+    // The iteration pretraining handed over to fine-tuning.
+    pub fine_tune_start: Option<usize>,
+    // Training losses that jumped well above the ones before them.
+    pub loss_spikes: Vec<(f64, f64)>,
+    pub status: Option<String>,
+    pub error: Option<String>,
+    pub models: Vec<ModelHandle>,
+    pub running: usize,
+    // Models that haven't reported they're ready yet.
+    pub initializing: usize,
+    pub cancel_generation: Arc<AtomicBool>,
+    // Set on the way out so a model still training stops between iterations.
+    pub stop_training: Arc<AtomicBool>,
     pub messages: Receiver<AppMessage>,
-    pub model_thread: JoinHandle<Result<(), VibeError>>,
+    pub logger: Option<Logger>,
+}
+
+// A model thread and the channel its commands go through. The first model is the main run, any others are
+// comparison runs that only train alongside it.
+pub struct ModelHandle {
+    pub commands: Sender<ModelCommandMessage>,
+    pub thread: JoinHandle<Result<(), VibeError>>,
+    // Disconnects when the thread ends, however it ends, so it can be waited on with a timeout.
+    pub done: Receiver<()>,
+    // Whether the model was built, one that failed has stopped and takes no commands.
+    pub built: bool,
 }
 
 #[derive(PartialEq)]
 pub enum State {
+    Initializing,
     Main,
     Training,
     Generate,
+    Stream,
     Exit,
 }
 
+impl State {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Initializing => "initializing",
+            Self::Main => "main",
+            Self::Training => "training",
+            Self::Generate => "generating",
+            Self::Stream => "streaming",
+            Self::Exit => "exiting",
+        }
+    }
+}
+
 impl App {
-    // Initialize the terminal, parse options, spawn event and model threads.
-    pub fn new() -> Result<Self, VibeError> {
+    // Initialize the terminal, spawn event and model threads.
+    pub fn new(mut options: Options) -> Result<Self, VibeError> {
         let backend = CrosstermBackend::new(io::stdout());
         let terminal = Terminal::new(backend).unwrap_or_else(|err| {
             panic!("unable to open terminal: {}", err);
         });
 
-        let mut options = Options::new();
-        options::parse_args(&mut options)?;
+        let baseline_loss_data = match &options.baseline {
+            Some(path) => loss_log::read_baseline(path)?,
+            None => Vec::new(),
+        };
+
+        let loss_stream = match &options.loss_fd {
+            Some(target) => Some(LossStream::open(target)?),
+            None => None,
+        };
 
-        let (commands_tx, commands_rx) = message::create_command_channel();
         let (data_tx, data_rx) = message::create_data_channel();
 
-        let data_tx_model = data_tx.clone();
-        let model_options = options.clone();
-        let model_thread = thread::spawn(move || model::run_model(commands_rx, data_tx_model, &model_options));
+        let cancel_generation = Arc::new(AtomicBool::new(false));
+        let stop_training = Arc::new(AtomicBool::new(false));
+        let runs: Vec<Options> = iter::once(options.clone()).chain(options.compare.as_deref().cloned()).collect();
+        let models: Vec<ModelHandle> = runs
+            .into_iter()
+            .enumerate()
+            .map(|(run, model_options)| {
+                let (commands_tx, commands_rx) = message::create_command_channel();
+                let data_tx_model = data_tx.clone();
+                let model_cancel = cancel_generation.clone();
+                let model_stop = stop_training.clone();
+                let (done_tx, done_rx) = mpsc::channel();
+                ModelHandle {
+                    commands: commands_tx,
+                    thread: thread::spawn(move || {
+                        let _done = done_tx;
+                        model::run_model(commands_rx, data_tx_model, &model_options, model_cancel, model_stop, run)
+                    }),
+                    done: done_rx,
+                    built: true,
+                }
+            })
+            .collect();
 
+        // Every key press is forwarded, the app decides what they do. A held or pasted key repeating faster than
+        // the cooldown is dropped so it can't flood the channel.
         thread::spawn(move || {
+            let mut last_key: Option<(KeyCode, Instant)> = None;
             loop {
-                if let Ok(event) = event::read() {
-                    if let Some(key) = event.as_key_press_event() {
-                        if let Err(_) = data_tx.send(AppMessage::Event(EventMessage::Key { event: key })) {
-                            break;
-                        }
+                let message = match event::read() {
+                    Ok(Event::Resize(width, height)) => EventMessage::Resize { width, height },
+                    // Moves and drags aren't used, only clicks and the wheel.
+                    Ok(Event::Mouse(mouse))
+                        if matches!(
+                            mouse.kind,
+                            MouseEventKind::Down(MouseButton::Left) | MouseEventKind::ScrollUp | MouseEventKind::ScrollDown
+                        ) =>
+                    {
+                        EventMessage::Mouse { event: mouse }
                     }
+                    Ok(event) => match event.as_key_press_event() {
+                        Some(key) => {
+                            if last_key.is_some_and(|(code, at)| code == key.code && at.elapsed() < KEY_COOLDOWN) {
+                                continue;
+                            }
+                            last_key = Some((key.code, Instant::now()));
+                            EventMessage::Key { event: key }
+                        }
+                        _ => continue,
+                    },
+                    Err(_) => continue,
+                };
+
+                if data_tx.send(AppMessage::Event(message)).is_err() {
+                    break;
                 }
             }
         });
 
+        // Settle on a color mode once, auto detection looks at the environment.
+        options.color_mode = options.color_mode.resolve();
+
+        let mut logger = match &options.log_file {
+            Some(path) => Some(Logger::open(path, options.log_level)?),
+            None => None,
+        };
+        if let Some(logger) = &mut logger {
+            logger.log(
+                Level::Info,
+                "start",
+                &[
+                    ("device", options.device.clone()),
+                    ("data", options.data.clone()),
+                    ("model", options.model_file.clone()),
+                    ("iterations", options.iterations.to_string()),
+                    ("batch_size", options.batch_size.to_string()),
+                    ("learn_rate", options.learn_rate.to_string()),
+                ],
+            );
+        }
+
+        let initializing = models.len();
+
         Ok(Self {
             terminal: terminal,
-            state: State::Main,
+            state: State::Initializing,
+            train_target: 0,
+            progress_times: VecDeque::new(),
+            batch_size: options.batch_size,
             show_generated: false,
+            grid_generated: false,
+            show_info: false,
+            show_score: false,
+            show_positions: false,
+            show_probe: false,
+            show_histograms: false,
+            show_floor: false,
+            generated_scroll: 0,
+            smoothing: options.smoothing,
+            click_targets: Vec::new(),
             loss_data: Vec::new(),
             validation_loss_data: Vec::new(),
+            evaluation_loss_data: Vec::new(),
+            baseline_loss_data,
+            compare_loss_data: Vec::new(),
+            compare_validation_loss_data: Vec::new(),
+            loss_stream,
             generated_data: Vec::new(),
-            model_commands: commands_tx,
+            generated_confidence: Vec::new(),
+            latest_batch: Vec::new(),
+            pinned_batch: None,
+            show_diff: false,
+            generation_summary: None,
+            generation_step: None,
+            stream_next: None,
+            description: None,
+            score: None,
+            position_losses: None,
+            probe: None,
+            histograms: None,
+            loss_floor: None,
+            live_sample: None,
+            saturation: None,
+            resumed: None,
+            fine_tune_start: None,
+            loss_spikes: Vec::new(),
+            status: None,
+            error: None,
+            models,
+            running: 0,
+            initializing,
+            cancel_generation,
+            stop_training,
             messages: data_rx,
             options: options,
-            model_thread: model_thread,
+            logger,
         })
     }
 
     // Draw the main interface screen.
     pub fn draw_main(&mut self) -> Result<(), VibeError> {
+        let view = MainView {
+            state: &self.state,
+            train_target: self.train_target,
+            throughput: self.throughput(),
+            batch_size: self.batch_size,
+            options: &self.options,
+            chart: LossChart {
+                loss_data: &self.loss_data,
+                validation_loss_data: &self.validation_loss_data,
+                evaluation_loss_data: &self.evaluation_loss_data,
+                baseline_loss_data: &self.baseline_loss_data,
+                compare_loss_data: &self.compare_loss_data,
+                compare_validation_loss_data: &self.compare_validation_loss_data,
+                loss_floor: if self.show_floor { self.loss_floor } else { None },
+                fine_tune_start: self.fine_tune_start,
+                loss_spikes: &self.loss_spikes,
+                saturation: self.saturation,
+                smoothing: self.smoothing,
+            },
+            generated: &self.generated_data,
+            generated_confidence: &self.generated_confidence,
+            generation_summary: self.generation_summary,
+            generation_step: &self.generation_step,
+            show_generated: self.show_generated,
+            grid_generated: self.grid_generated,
+            latest_batch: &self.latest_batch,
+            pinned_batch: &self.pinned_batch,
+            show_diff: self.show_diff,
+            description: &self.description,
+            show_info: self.show_info,
+            score: &self.score,
+            show_score: self.show_score,
+            position_losses: &self.position_losses,
+            show_positions: self.show_positions,
+            probe: &self.probe,
+            show_probe: self.show_probe,
+            histograms: &self.histograms,
+            show_histograms: self.show_histograms,
+            live_sample: &self.live_sample,
+            generated_scroll: self.generated_scroll,
+            status: &self.status,
+            error: &self.error,
+        };
+        let mut click_targets = Vec::new();
         self.terminal.draw(|frame| {
-            main_screen::draw(
-                frame,
-                &self.options,
-                &self.loss_data,
-                &self.validation_loss_data,
-                &self.generated_data,
-                self.show_generated,
-            )
+            click_targets = main_screen::draw(frame, &view);
+            self.options.color_mode.downgrade(frame.buffer_mut());
         })?;
+        self.click_targets = click_targets;
         Ok(())
     }
 
+    // Wait for the next message, then drain everything else already queued so a burst from the model is
+    // handled in one batch instead of one redraw per message.
+    //
+    // While streaming, the wait is cut short when the next batch is due. While initializing, it's cut short to
+    // redraw the spinner.
     fn handle_messages(&mut self) -> Result<(), VibeError> {
-        match self.messages.recv()? {
-            AppMessage::Model(message) => {
-                self.process_model_message(message)?;
+        let due = match self.stream_next {
+            Some(due) => Some(due),
+            None if self.state == State::Initializing => Some(Instant::now() + SPINNER_INTERVAL),
+            None => None,
+        };
+        let mut message = match due {
+            Some(due) => match self.messages.recv_timeout(due.saturating_duration_since(Instant::now())) {
+                Ok(message) => message,
+                Err(RecvTimeoutError::Timeout) if self.stream_next.is_some() => return self.request_stream(),
+                Err(RecvTimeoutError::Timeout) => return Ok(()),
+                Err(RecvTimeoutError::Disconnected) => return Err(VibeError::new("message channel disconnected")),
+            },
+            None => self.messages.recv()?,
+        };
+        loop {
+            match message {
+                AppMessage::Model(message) => {
+                    self.process_model_message(message)?;
+                }
+
+                AppMessage::Event(message) => {
+                    self.process_event_message(message)?;
+                }
             }
 
-            AppMessage::Event(message) => {
-                self.process_event_message(message)?;
+            if self.state == State::Exit {
+                break;
+            }
+
+            message = match self.messages.try_recv() {
+                Ok(message) => message,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => return Err(VibeError::new("message channel disconnected")),
+            };
+        }
+
+        Ok(())
+    }
+
+    fn log(&mut self, level: Level, event: &str, fields: &[(&str, String)]) {
+        if let Some(logger) = &mut self.logger {
+            logger.log(level, event, fields);
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        if state != self.state {
+            self.log(
+                Level::Info,
+                "state",
+                &[("from", self.state.name().to_string()), ("to", state.name().to_string())],
+            );
+        }
+        self.state = state;
+    }
+
+    // Training examples per second over the recent progress messages.
+    //
+    // NOTE: progress arrives in bursts when the app is busy drawing, so a single interval between messages is
+    // too noisy, the rate is taken across the whole window instead.
+    fn throughput(&self) -> Option<f64> {
+        let ((first_time, first_iteration), (last_time, last_iteration)) = (self.progress_times.front()?, self.progress_times.back()?);
+        let elapsed = last_time.duration_since(*first_time).as_secs_f64();
+        if elapsed <= 0. {
+            return None;
+        }
+
+        Some((last_iteration - first_iteration) as f64 * self.batch_size as f64 / elapsed)
+    }
+
+    // Send a command to the main model.
+    fn send_main(&self, command: ModelCommandMessage) -> Result<(), VibeError> {
+        self.models[0].commands.send(command)?;
+        Ok(())
+    }
+
+    // Send a command to every model that was built, comparison runs included, returning how many were sent it.
+    fn send_all(&self, command: ModelCommandMessage) -> Result<usize, VibeError> {
+        let mut sent = 0;
+        for model in self.models.iter().filter(|model| model.built) {
+            model.commands.send(command.clone())?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    // Mark one of the running models as done, going back to the main state once none are left or quitting
+    // when asked to exit on finish.
+    fn finish_run(&mut self) -> Result<(), VibeError> {
+        self.running = self.running.saturating_sub(1);
+        if self.running == 0 {
+            if self.options.exit_on_finish && matches!(self.state, State::Training | State::Generate) {
+                self.set_state(State::Exit);
+            } else {
+                self.set_state(State::Main);
             }
         }
+        Ok(())
+    }
+
+    // The mean of the recent training losses when a new one spikes above them, None while there are too few
+    // losses to tell.
+    fn spike_mean(&self, loss: f64) -> Option<f64> {
+        let window = &self.loss_data[self.loss_data.len().checked_sub(SPIKE_WINDOW)?..];
+        let mean = window.iter().map(|(_, loss)| loss).sum::<f64>() / SPIKE_WINDOW as f64;
+        let variance = window.iter().map(|(_, loss)| (loss - mean).powi(2)).sum::<f64>() / SPIKE_WINDOW as f64;
+
+        let deviation = variance.sqrt().max(mean * SPIKE_MIN_DEVIATION);
+
+        (loss > mean + SPIKE_SIGMA * deviation).then_some(mean)
+    }
+
+    // Train every model for another round of iterations, continuing on from the last one.
+    fn start_training(&mut self) -> Result<(), VibeError> {
+        self.error = None;
+        let start = match self.loss_data.last() {
+            Some((iteration, _)) => *iteration as usize,
+            None => self.resumed.unwrap_or(0),
+        };
+        // Only the first training run pretrains, a resumed one only has the iterations it had left to finish.
+        let (iterations, pretrain) = match (self.loss_data.is_empty(), self.resumed, &self.options.pretrain_data) {
+            (true, Some(resumed), _) => (self.options.iterations.saturating_sub(resumed), 0),
+            (true, None, Some(_)) => (self.options.iterations, self.options.pretrain_iterations),
+            _ => (self.options.iterations, 0),
+        };
+        self.train_target = start + pretrain + iterations;
+        self.progress_times.clear();
+        self.running = self.send_all(ModelCommandMessage::Train {
+            iterations,
+            start,
+            pretrain,
+        })?;
+        self.set_state(State::Training);
+        Ok(())
+    }
+
+    // Move every model between the cpu and whichever gpu is available, only between runs.
+    fn switch_device(&mut self) -> Result<(), VibeError> {
+        if self.state != State::Main {
+            return Ok(());
+        }
+
+        let name = if self.options.device == device::DEVICE_NAME_CPU {
+            device::find_default()
+        } else {
+            device::DEVICE_NAME_CPU.to_string()
+        };
+        if name == self.options.device {
+            self.error = Some("no cuda or metal gpu is available to switch to".to_string());
+            return Ok(());
+        }
+
+        self.error = None;
+        self.send_all(ModelCommandMessage::SwitchDevice { name })?;
+        Ok(())
+    }
 
+    // Ask the model for the next batch of streamed words.
+    fn request_stream(&mut self) -> Result<(), VibeError> {
+        self.stream_next = None;
+        self.running = 1;
+        self.send_main(ModelCommandMessage::Vibe { count: STREAM_BATCH })?;
         Ok(())
     }
 
     // Process user input.
     fn process_event_message(&mut self, event: EventMessage) -> Result<(), VibeError> {
         match event {
-            EventMessage::Key { event } => match event.code {
-                KeyCode::Char('t') | KeyCode::Enter => {
-                    if self.state == State::Main {
-                        self.model_commands.send(ModelCommandMessage::Train {
-                            iterations: self.options.iterations,
-                            start: self.loss_data.last().unwrap_or(&(0., 0.)).0 as usize,
-                        })?;
-                        self.state = State::Training;
-                    }
+            // Unbound keys do nothing, except end a stream like any other key.
+            EventMessage::Key { event } => {
+                let action = self.options.key_bindings.action(event.code);
+                if action.is_some() || self.state == State::Stream {
+                    self.process_action(action)?;
                 }
+            }
 
-                KeyCode::Char('v') => {
-                    if self.state == State::Main {
-                        self.model_commands.send(ModelCommandMessage::Vibe {
-                            count: self.options.generate,
-                        })?;
-                        self.state = State::Generate;
+            // Clicking a keybinding does the same as pressing it, the wheel scrolls the generated list.
+            EventMessage::Mouse { event } => match event.kind {
+                MouseEventKind::Down(MouseButton::Left) => {
+                    let position = Position::new(event.column, event.row);
+                    if let Some((_, action)) = self.click_targets.iter().find(|(area, _)| area.contains(position)) {
+                        self.process_action(Some(*action))?;
                     }
                 }
+                MouseEventKind::ScrollDown if self.show_generated => {
+                    self.generated_scroll = (self.generated_scroll + 1).min(self.generated_data.len().saturating_sub(1));
+                }
+                MouseEventKind::ScrollUp if self.show_generated => {
+                    self.generated_scroll = self.generated_scroll.saturating_sub(1);
+                }
+                _ => {}
+            },
+
+            // Relayout for the new size, clearing anything left over from the old layout.
+            EventMessage::Resize { width, height } => {
+                self.terminal.resize(Rect::new(0, 0, width, height))?;
+            }
+        }
 
-                KeyCode::Char('p') => {
-                    self.show_generated = !self.show_generated;
+        Ok(())
+    }
+
+    // Carry out the action of a key press or click.
+    fn process_action(&mut self, action: Option<Action>) -> Result<(), VibeError> {
+        match action {
+            // Anything but quit ends a stream. A batch still generating finishes before going back to the main
+            // state.
+            _ if self.state == State::Stream && action != Some(Action::Quit) => {
+                if self.stream_next.take().is_some() {
+                    self.set_state(State::Main);
+                } else {
+                    self.set_state(State::Generate);
                 }
+            }
 
-                KeyCode::Char('s') => {
-                    self.model_commands.send(ModelCommandMessage::Save)?;
+            Some(Action::Train) if self.state == State::Main => {
+                self.start_training()?;
+            }
+
+            Some(Action::Vibe) if self.state == State::Main => {
+                self.error = None;
+                self.running = 1;
+                self.latest_batch.clear();
+                self.generated_scroll = 0;
+                self.send_main(ModelCommandMessage::Vibe {
+                    count: self.options.generate,
+                })?;
+                self.set_state(State::Generate);
+            }
+
+            Some(Action::Stream) if self.state == State::Main => {
+                self.error = None;
+                self.show_generated = false;
+                self.request_stream()?;
+                self.set_state(State::Stream);
+            }
+
+            // The model thread is busy generating and won't read commands until it's done, so the cancel
+            // goes through a shared flag it checks between words.
+            Some(Action::Cancel) if self.state == State::Generate => {
+                self.cancel_generation.store(true, Ordering::Relaxed);
+            }
+
+            Some(Action::Reload) if self.state == State::Main => {
+                self.error = None;
+                self.loss_floor = None;
+                self.send_all(ModelCommandMessage::ReloadData)?;
+                if self.show_floor {
+                    self.send_main(ModelCommandMessage::LossFloor)?;
                 }
+            }
+
+            Some(Action::Popup) => {
+                self.show_generated = !self.show_generated;
+            }
+
+            Some(Action::Grid) => {
+                self.grid_generated = !self.grid_generated;
+            }
+
+            // Keep the latest batch to compare the next ones against.
+            Some(Action::Pin) => {
+                self.pinned_batch = Some(self.latest_batch.clone());
+                self.status = Some(format!("pinned {} vibes to diff against", self.latest_batch.len()));
+            }
 
-                KeyCode::Char('q') | KeyCode::Esc => {
-                    self.model_commands.send(ModelCommandMessage::Shutdown)?;
-                    self.state = State::Exit;
+            Some(Action::Diff) => {
+                self.show_diff = !self.show_diff;
+            }
+
+            Some(Action::Score) => match &self.options.score {
+                Some(word) => {
+                    self.show_score = !self.show_score;
+                    if self.show_score {
+                        self.send_main(ModelCommandMessage::Score { word: word.clone() })?;
+                    }
+                }
+                None => {
+                    self.error = Some("no word to score, pass one with --score".to_string());
                 }
+            },
 
-                _ => {}
+            Some(Action::Probe) => match &self.options.probe {
+                Some(context) => {
+                    self.show_probe = !self.show_probe;
+                    if self.show_probe {
+                        self.send_main(ModelCommandMessage::Probe { context: context.clone() })?;
+                    }
+                }
+                None => {
+                    self.error = Some("no context to probe, pass one with --probe".to_string());
+                }
             },
+
+            Some(Action::Histograms) => {
+                if self.options.debug_histograms > 0 {
+                    self.show_histograms = !self.show_histograms;
+                } else {
+                    self.error = Some("no histograms are recorded, turn them on with --debug-histograms".to_string());
+                }
+            }
+
+            Some(Action::Smooth) => {
+                self.smoothing = (self.smoothing + SMOOTHING_STEP).min(MAX_SMOOTHING);
+            }
+
+            Some(Action::Unsmooth) => {
+                self.smoothing = self.smoothing.saturating_sub(SMOOTHING_STEP).max(1);
+            }
+
+            Some(Action::Floor) => {
+                self.show_floor = !self.show_floor;
+                if self.show_floor && self.loss_floor.is_none() {
+                    self.send_main(ModelCommandMessage::LossFloor)?;
+                }
+            }
+
+            Some(Action::Positions) => {
+                self.show_positions = !self.show_positions;
+                if self.show_positions {
+                    self.send_main(ModelCommandMessage::PositionLosses)?;
+                }
+            }
+
+            Some(Action::Info) => {
+                self.show_info = !self.show_info;
+                if self.show_info {
+                    self.send_main(ModelCommandMessage::Describe)?;
+                }
+            }
+
+            Some(Action::Device) => {
+                self.switch_device()?;
+            }
+
+            Some(Action::Save) => {
+                self.send_all(ModelCommandMessage::Save)?;
+            }
+
+            // The models are shut down once the main loop ends.
+            Some(Action::Quit) => {
+                self.set_state(State::Exit);
+            }
+
+            // Actions that don't apply in the current state do nothing.
+            Some(_) | None => {}
         }
 
         Ok(())
@@ -159,31 +739,247 @@ impl App {
     // Process all training messages.
     fn process_model_message(&mut self, message: ModelResultMessage) -> Result<(), VibeError> {
         match message {
+            // Comparison runs are only charted, the streams and logs follow the main run.
             ModelResultMessage::Progress {
+                run,
                 loss_type,
                 iteration,
                 loss,
-            } => match loss_type {
+            } if run > 0 => match loss_type {
                 LossType::Training => {
-                    self.loss_data.push((iteration as f64, loss as f64));
+                    self.compare_loss_data.push((iteration as f64, loss as f64));
                 }
                 LossType::Validation => {
-                    self.validation_loss_data.push((iteration as f64, loss as f64));
+                    self.compare_validation_loss_data.push((iteration as f64, loss as f64));
                 }
+                LossType::Evaluation => {}
             },
 
-            ModelResultMessage::Generated { text } => {
-                self.generated_data.push(text);
+            ModelResultMessage::Progress {
+                loss_type,
+                iteration,
+                loss,
+                ..
+            } => {
+                // A closed reader shouldn't interrupt training, report it and stop streaming.
+                if let Some(loss_stream) = &mut self.loss_stream
+                    && let Err(err) = loss_stream.write(&loss_type, iteration, loss)
+                {
+                    self.error = Some(format!("stopped streaming losses: {}", err));
+                    self.loss_stream = None;
+                    self.log(Level::Warn, "loss_stream", &[("error", err.to_string())]);
+                }
+
+                let (level, kind) = match loss_type {
+                    LossType::Training => (Level::Debug, loss_log::KIND_TRAINING),
+                    LossType::Validation => (Level::Info, loss_log::KIND_VALIDATION),
+                    LossType::Evaluation => (Level::Info, loss_log::KIND_EVALUATION),
+                };
+                self.log(
+                    level,
+                    "loss",
+                    &[
+                        ("kind", kind.to_string()),
+                        ("iteration", iteration.to_string()),
+                        ("loss", loss.to_string()),
+                    ],
+                );
+
+                match loss_type {
+                    LossType::Training => {
+                        self.progress_times.push_back((Instant::now(), iteration));
+                        if self.progress_times.len() > THROUGHPUT_WINDOW {
+                            self.progress_times.pop_front();
+                        }
+                        if let Some(mean) = self.spike_mean(loss as f64) {
+                            self.loss_spikes.push((iteration as f64, loss as f64));
+                            self.log(
+                                Level::Warn,
+                                "loss_spike",
+                                &[
+                                    ("iteration", iteration.to_string()),
+                                    ("loss", loss.to_string()),
+                                    ("mean", mean.to_string()),
+                                ],
+                            );
+                        }
+                        self.loss_data.push((iteration as f64, loss as f64));
+                    }
+                    LossType::Validation => {
+                        self.validation_loss_data.push((iteration as f64, loss as f64));
+                    }
+                    LossType::Evaluation => {
+                        self.evaluation_loss_data.push((iteration as f64, loss as f64));
+                    }
+                }
+            }
+
+            ModelResultMessage::Generated {
+                text,
+                tag,
+                confidence,
+                nearest,
+            } => {
+                if self.state == State::Generate {
+                    self.latest_batch.push(text.clone());
+                }
+
+                let text = match nearest {
+                    Some((_, 0)) => format!("{}  (in the training data)", text),
+                    Some((word, 1)) => format!("{}  (1 edit from {})", text, word),
+                    Some((word, distance)) => format!("{}  ({} edits from {})", text, distance, word),
+                    None => text,
+                };
+
+                match tag {
+                    Some(tag) => {
+                        let tag = format!("[{}] ", tag);
+                        let tag_confidence = std::iter::repeat_n(f32::NAN, tag.chars().count());
+                        self.generated_confidence.push(tag_confidence.chain(confidence).collect());
+                        self.generated_data.push(tag + &text);
+                    }
+                    None => {
+                        self.generated_confidence.push(confidence);
+                        self.generated_data.push(text);
+                    }
+                }
+
+                if self.state == State::Stream && self.generated_data.len() > STREAM_LINES {
+                    self.generated_data.drain(..self.generated_data.len() - STREAM_LINES);
+                    self.generated_confidence.drain(..self.generated_confidence.len() - STREAM_LINES);
+                }
+            }
+
+            ModelResultMessage::GenerationSummary { summary } => {
+                self.log(
+                    Level::Info,
+                    "generated",
+                    &[
+                        ("count", summary.count.to_string()),
+                        ("unique", summary.unique.to_string()),
+                        ("rejected", summary.rejected.to_string()),
+                        ("banned", summary.banned.to_string()),
+                    ],
+                );
+                self.generation_summary = Some(summary);
+            }
+
+            ModelResultMessage::GenerationStep { step } => {
+                self.generation_step = Some(step);
+            }
+
+            ModelResultMessage::Description { text, layers } => {
+                self.description = Some((text, layers));
+            }
+
+            ModelResultMessage::Scored { word, steps } => {
+                self.score = Some((word, steps));
+            }
+
+            // Only the main run's histograms are shown when comparing.
+            ModelResultMessage::Histograms {
+                run,
+                iteration,
+                weights,
+                gradients,
+            } => {
+                if run == 0 {
+                    self.histograms = Some((iteration, weights, gradients));
+                }
+            }
+
+            ModelResultMessage::Probed { context, distribution } => {
+                self.probe = Some((context, distribution));
+            }
+
+            ModelResultMessage::LossFloor { loss } => {
+                self.loss_floor = Some(loss);
             }
 
-            // TODO: errors should be displayed separately from generated text.
-            ModelResultMessage::Error { err } => {
-                self.generated_data.push(err.to_string());
-                self.state = State::Main;
+            ModelResultMessage::BatchSize { run, size } => {
+                if run == 0 {
+                    self.batch_size = size;
+                }
+            }
+
+            ModelResultMessage::Saturation { run, iteration, fraction } => {
+                if run == 0 {
+                    self.saturation = Some((iteration, fraction));
+                }
+            }
+
+            ModelResultMessage::LiveSample { run, iteration, text } => {
+                if run == 0 {
+                    self.live_sample = Some((iteration, text));
+                }
+            }
+
+            ModelResultMessage::DeviceSwitched { run, name } => {
+                if run == 0 {
+                    self.log(Level::Info, "device", &[("name", name.clone())]);
+                    self.status = Some(format!("moved the model and data to the {}", name));
+                    self.options.device = name;
+                }
+            }
+
+            ModelResultMessage::Resumed { run, iteration } => {
+                if run == 0 {
+                    self.resumed = Some(iteration);
+                }
+            }
+
+            ModelResultMessage::FineTune { run, iteration } => {
+                if run == 0 {
+                    self.fine_tune_start = Some(iteration);
+                    self.status = Some(format!("pretraining finished, fine-tuning from iteration {}", iteration));
+                }
+            }
+
+            ModelResultMessage::PositionLosses { losses } => {
+                self.position_losses = Some(losses);
+            }
+
+            // Messages from the comparison run say so, they'd read as the main run's otherwise.
+            ModelResultMessage::Status { run, text } => {
+                self.log(Level::Info, "status", &[("run", run.to_string()), ("text", text.clone())]);
+                self.status = Some(run_text(run, text));
+            }
+
+            ModelResultMessage::Error { run, err } => {
+                self.log(Level::Error, "error", &[("run", run.to_string()), ("text", err.to_string())]);
+                self.error = Some(run_text(run, err.to_string()));
+                self.generation_step = None;
+                // Errors while the models start up aren't from a run, Ready still follows them.
+                if self.state != State::Initializing {
+                    self.finish_run()?;
+                }
+            }
+
+            // Every model reports Ready once, even one that failed to build.
+            ModelResultMessage::Ready { run, built } => {
+                if let Some(model) = self.models.get_mut(run) {
+                    model.built = built;
+                }
+                self.initializing = self.initializing.saturating_sub(1);
+                if self.initializing == 0 {
+                    if self.state == State::Initializing {
+                        self.set_state(State::Main);
+                    }
+                    // A resumed run carries on by itself unless it had already finished.
+                    let resuming = self.resumed.is_some_and(|iteration| iteration < self.options.iterations);
+                    if (self.options.autostart || resuming) && self.state == State::Main {
+                        self.start_training()?;
+                    }
+                }
             }
 
             ModelResultMessage::Finished => {
-                self.state = State::Main;
+                self.generation_step = None;
+                if self.state == State::Stream {
+                    self.stream_next = Some(Instant::now() + STREAM_INTERVAL);
+                } else {
+                    self.finish_run()?;
+                }
             }
         }
 
@@ -193,22 +989,89 @@ impl App {
     // App state machine.
     pub fn run(mut self) -> Result<(), VibeError> {
         enable_raw_mode()?;
-        execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+        if self.options.alt_screen {
+            execute!(self.terminal.backend_mut(), EnterAlternateScreen)?;
+        }
+        if self.options.mouse {
+            execute!(self.terminal.backend_mut(), EnableMouseCapture)?;
+        }
 
-        loop {
-            if self.state == State::Exit {
-                _ = self.model_thread.join();
-                break;
-            }
+        // The terminal is put back and every model thread stopped however the loop ends, the first error is
+        // returned once they're all done.
+        let result = self.main_loop();
+        let restored = self.restore_terminal();
+        let joined = self.shutdown_models();
+        result?;
+        restored?;
+        joined?;
+
+        // NOTE: scripts running with exit on finish need the last error to fail the process, otherwise it's only
+        // been shown in the interface that just closed.
+        if self.options.exit_on_finish
+            && let Some(error) = self.error
+        {
+            return Err(VibeError::new(error));
+        }
+
+        Ok(())
+    }
+
+    // Stop whatever the models are doing and wait for each of their threads to end, comparison runs included.
+    fn shutdown_models(&mut self) -> Result<(), VibeError> {
+        self.stop_training.store(true, Ordering::Relaxed);
+        self.cancel_generation.store(true, Ordering::Relaxed);
+        // A model that failed to build has already stopped listening.
+        for model in &self.models {
+            _ = model.commands.send(ModelCommandMessage::Shutdown);
+        }
 
+        self.models.drain(..).map(join_model_thread).fold(Ok(()), Result::and)
+    }
+
+    // Draw and handle messages until it's time to exit.
+    fn main_loop(&mut self) -> Result<(), VibeError> {
+        while self.state != State::Exit {
             self.draw_main()?;
             self.handle_messages()?;
         }
+        Ok(())
+    }
 
+    // Put the terminal back the way it was found.
+    fn restore_terminal(&mut self) -> Result<(), VibeError> {
+        if self.options.mouse {
+            execute!(self.terminal.backend_mut(), DisableMouseCapture)?;
+        }
         disable_raw_mode()?;
-        execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        if self.options.alt_screen {
+            execute!(self.terminal.backend_mut(), LeaveAlternateScreen)?;
+        } else {
+            // The last frame stays in the normal buffer, so park the cursor below it for the shell prompt.
+            let size = self.terminal.size()?;
+            self.terminal.set_cursor_position((0, size.height.saturating_sub(1)))?;
+            println!();
+        }
         self.terminal.show_cursor()?;
-
         Ok(())
     }
 }
+
+// Label text from a comparison run with where it came from.
+fn run_text(run: usize, text: String) -> String {
+    match run {
+        0 => text,
+        _ => format!("compare: {}", text),
+    }
+}
+
+// Wait for the model thread to finish, returning the error it stopped with if any.
+fn join_model_thread(model: ModelHandle) -> Result<(), VibeError> {
+    if let Err(RecvTimeoutError::Timeout) = model.done.recv_timeout(SHUTDOWN_TIMEOUT) {
+        return Err(VibeError::new(format!(
+            "model thread did not shut down within {} seconds",
+            SHUTDOWN_TIMEOUT.as_secs()
+        )));
+    }
+
+    model.thread.join().map_err(|_| VibeError::new("model thread panicked"))?
+}
@@ -5,6 +5,11 @@ use candle_core::Device;
 pub const DEVICE_NAME_CPU: &str = "cpu";
 pub const DEVICE_NAME_CUDA: &str = "cuda";
 pub const DEVICE_NAME_METAL: &str = "metal";
+pub const DEVICE_NAME_AUTO: &str = "auto";
+pub const DEVICE_NAME_GPU: &str = "gpu";
+
+// Other names people use for the devices.
+const DEVICE_ALIASES: [(&str, &str); 2] = [("nvidia", DEVICE_NAME_CUDA), ("mps", DEVICE_NAME_METAL)];
 
 // Determine a default device to use.
 pub fn find_default() -> String {
@@ -21,9 +26,45 @@ pub fn find_default() -> String {
     return DEVICE_NAME_CPU.to_string();
 }
 
+// Map a device name, ignoring case and surrounding whitespace, to the canonical name of the device to use.
+//
+// NOTE: `auto` picks whatever is available like the default does, `gpu` does the same but refuses to fall back
+// to the cpu.
+pub fn normalize(device: &str) -> Result<String, VibeError> {
+    let name = device.trim().to_lowercase();
+    let name = DEVICE_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name.as_str(), |(_, canonical)| canonical);
+
+    match name {
+        DEVICE_NAME_CPU | DEVICE_NAME_CUDA | DEVICE_NAME_METAL => Ok(name.to_string()),
+        DEVICE_NAME_AUTO => Ok(find_default()),
+        DEVICE_NAME_GPU => match find_default() {
+            default if default == DEVICE_NAME_CPU => Err(VibeError::new("no cuda or metal gpu is available")),
+            default => Ok(default),
+        },
+        _ => Err(VibeError::new(format!(
+            "invalid device: {}, expected one of {}",
+            device.trim(),
+            [
+                DEVICE_NAME_AUTO,
+                DEVICE_NAME_CPU,
+                DEVICE_NAME_CUDA,
+                DEVICE_NAME_METAL,
+                DEVICE_NAME_GPU
+            ]
+            .into_iter()
+            .chain(DEVICE_ALIASES.iter().map(|(alias, _)| *alias))
+            .collect::<Vec<&str>>()
+            .join(", ")
+        ))),
+    }
+}
+
 // Open the given device for data processing.
 pub fn open_device(device: &String) -> Result<Device, VibeError> {
-    match device.trim().to_lowercase().as_str() {
+    match normalize(device)?.as_str() {
         DEVICE_NAME_CPU => Ok(Device::Cpu),
         DEVICE_NAME_CUDA => Ok(Device::new_cuda(0).map_err(|e| VibeError::new(format!("unable to open cuda device: {}", e)))?),
         DEVICE_NAME_METAL => Ok(Device::new_metal(0).map_err(|e| VibeError::new(format!("unable to open metal device: {}", e)))?),
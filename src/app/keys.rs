@@ -0,0 +1,213 @@
+use crate::error::VibeError;
+
+use crossterm::event::KeyCode;
+use std::fs;
+
+// User actions that can be bound to keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Train,
+    Vibe,
+    Stream,
+    Cancel,
+    Reload,
+    Popup,
+    Grid,
+    Pin,
+    Diff,
+    Score,
+    Probe,
+    Positions,
+    Histograms,
+    Floor,
+    Smooth,
+    Unsmooth,
+    Info,
+    Device,
+    Save,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Self; 20] = [
+        Self::Train,
+        Self::Vibe,
+        Self::Stream,
+        Self::Cancel,
+        Self::Reload,
+        Self::Popup,
+        Self::Grid,
+        Self::Pin,
+        Self::Diff,
+        Self::Score,
+        Self::Probe,
+        Self::Positions,
+        Self::Histograms,
+        Self::Floor,
+        Self::Smooth,
+        Self::Unsmooth,
+        Self::Info,
+        Self::Device,
+        Self::Save,
+        Self::Quit,
+    ];
+
+    // The name used for the action in bindings.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Train => "train",
+            Self::Vibe => "vibe",
+            Self::Stream => "stream",
+            Self::Cancel => "cancel",
+            Self::Reload => "reload",
+            Self::Popup => "popup",
+            Self::Grid => "grid",
+            Self::Pin => "pin",
+            Self::Diff => "diff",
+            Self::Score => "score",
+            Self::Probe => "probe",
+            Self::Positions => "positions",
+            Self::Histograms => "histograms",
+            Self::Floor => "floor",
+            Self::Smooth => "smooth",
+            Self::Unsmooth => "unsmooth",
+            Self::Info => "info",
+            Self::Device => "device",
+            Self::Save => "save",
+            Self::Quit => "quit",
+        }
+    }
+
+    fn from_name(name: &str) -> Result<Self, VibeError> {
+        Self::ALL.into_iter().find(|action| action.name() == name).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|action| action.name()).collect();
+            VibeError::new(format!("unknown action {}, expected one of {}", name, names.join(", ")))
+        })
+    }
+}
+
+// The keys bound to each action, in display order.
+#[derive(Debug, Clone)]
+pub struct KeyBindings {
+    bindings: Vec<(Action, Vec<KeyCode>)>,
+}
+
+impl KeyBindings {
+    pub fn new() -> Self {
+        Self {
+            bindings: vec![
+                (Action::Train, vec![KeyCode::Char('t'), KeyCode::Enter]),
+                (Action::Vibe, vec![KeyCode::Char('v')]),
+                (Action::Stream, vec![KeyCode::Char('m')]),
+                (Action::Cancel, vec![KeyCode::Char('x')]),
+                (Action::Reload, vec![KeyCode::Char('r')]),
+                (Action::Popup, vec![KeyCode::Char('p')]),
+                (Action::Grid, vec![KeyCode::Char('g')]),
+                (Action::Pin, vec![KeyCode::Char('n')]),
+                (Action::Diff, vec![KeyCode::Char('d')]),
+                (Action::Score, vec![KeyCode::Char('c')]),
+                (Action::Probe, vec![KeyCode::Char('o')]),
+                (Action::Positions, vec![KeyCode::Char('l')]),
+                (Action::Histograms, vec![KeyCode::Char('h')]),
+                (Action::Floor, vec![KeyCode::Char('f')]),
+                (Action::Smooth, vec![KeyCode::Char('+'), KeyCode::Char('=')]),
+                (Action::Unsmooth, vec![KeyCode::Char('-')]),
+                (Action::Info, vec![KeyCode::Char('i')]),
+                (Action::Device, vec![KeyCode::Char('w')]),
+                (Action::Save, vec![KeyCode::Char('s')]),
+                (Action::Quit, vec![KeyCode::Char('q'), KeyCode::Esc]),
+            ],
+        }
+    }
+
+    pub fn bindings(&self) -> &[(Action, Vec<KeyCode>)] {
+        &self.bindings
+    }
+
+    // Find the action bound to a key.
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&code))
+            .map(|(action, _)| *action)
+    }
+
+    // Replace the keys of an action from an `action=key[,key]` binding, e.g. `train=space,enter`.
+    pub fn bind(&mut self, binding: &str) -> Result<(), VibeError> {
+        let (name, keys) = binding
+            .split_once('=')
+            .ok_or_else(|| VibeError::new(format!("invalid key binding {}, expected action=key", binding)))?;
+
+        let action = Action::from_name(name.trim())?;
+        let keys = keys.split(',').map(parse_key).collect::<Result<Vec<KeyCode>, VibeError>>()?;
+
+        if let Some((_, bound)) = self.bindings.iter_mut().find(|(bound_action, _)| *bound_action == action) {
+            *bound = keys;
+        }
+
+        Ok(())
+    }
+
+    // Apply every binding in a file, one `action=key[,key]` per line with `#` comments.
+    pub fn load(&mut self, path: &String) -> Result<(), VibeError> {
+        let content = fs::read_to_string(path).map_err(|e| VibeError::new(format!("unable to open {}: {}", path, e)))?;
+
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if !line.is_empty() {
+                self.bind(line)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Make sure no key is bound to more than one action.
+    pub fn validate(&self) -> Result<(), VibeError> {
+        for (index, (action, keys)) in self.bindings.iter().enumerate() {
+            for (other_action, other_keys) in &self.bindings[index + 1..] {
+                if let Some(key) = keys.iter().find(|key| other_keys.contains(key)) {
+                    return Err(VibeError::new(format!(
+                        "key {} is bound to both {} and {}",
+                        format_key(key),
+                        action.name(),
+                        other_action.name()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Parse a key name, either a single character or one of the named keys.
+fn parse_key(name: &str) -> Result<KeyCode, VibeError> {
+    let name = name.trim();
+    let mut chars = name.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(letter), None) => Ok(KeyCode::Char(letter)),
+        _ => match name.to_lowercase().as_str() {
+            "space" => Ok(KeyCode::Char(' ')),
+            "enter" => Ok(KeyCode::Enter),
+            "esc" => Ok(KeyCode::Esc),
+            "tab" => Ok(KeyCode::Tab),
+            "backspace" => Ok(KeyCode::Backspace),
+            _ => Err(VibeError::new(format!("invalid key: {}", name))),
+        },
+    }
+}
+
+// Format a key the same way it's named in bindings.
+pub fn format_key(key: &KeyCode) -> String {
+    match key {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(letter) => letter.to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        _ => key.to_string().to_lowercase(),
+    }
+}
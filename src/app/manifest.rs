@@ -0,0 +1,92 @@
+use crate::{app::options::Options, error::VibeError};
+
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// The path that writes the manifest to stdout instead of a file.
+pub const STDOUT_PATH: &str = "-";
+
+// Every line that replays one of the flags starts with this.
+const ARG_PREFIX: &str = "arg=";
+
+// FNV-1a, a small hash that stays the same across builds and platforms, unlike the standard library's.
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(FNV_OFFSET, |hash, &byte| (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME))
+}
+
+// Write a manifest of the run, the flags it was started with and what they resolved to.
+//
+// The flags are one `arg=` line each so values with spaces survive, followed by the resolved device so an `auto`
+// run replays on the same device, and the resolved training seed so it trains on the same batches. Everything
+// else is there to compare runs by, the full options are written as comments since only the flags are read back.
+//
+// NOTE: the training seed pins the shuffle and batches but not the initial weights, so a replayed run only
+// retraces the same losses when it starts from the same model file or autosave. Generation is pinned separately
+// with --generate-seed.
+pub fn write(options: &Options, path: &str) -> Result<(), VibeError> {
+    let data = fs::read(&options.data).map_err(|e| VibeError::new(format!("unable to open {}: {}", options.data, e)))?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+
+    let mut lines: Vec<String> = vec![
+        "# gpturd run manifest, replay it with --from-manifest".to_string(),
+        format!("version={}", env!("CARGO_PKG_VERSION")),
+        format!("candle={}", env!("CANDLE_VERSION")),
+        format!("timestamp={}", timestamp),
+        format!("device={}", options.device),
+        format!(
+            "generate_seed={}",
+            options.generate_seed.map_or("none".to_string(), |seed| seed.to_string())
+        ),
+        format!(
+            "train_seed={}",
+            options.train_seed.map_or("none".to_string(), |seed| seed.to_string())
+        ),
+        format!("data={}", options.data),
+        format!("data_fnv1a={:016x}", fnv1a(&data)),
+    ];
+
+    // The manifest flag itself isn't replayed, so a replay doesn't overwrite the manifest it came from, and
+    // neither is asking for help.
+    let mut flags = options.args.iter();
+    while let Some(flag) = flags.next() {
+        if flag == "--manifest" {
+            flags.next();
+        } else if !flag.starts_with("--manifest=") && !matches!(flag.as_str(), "--help" | "-h") {
+            lines.push(format!("{}{}", ARG_PREFIX, flag));
+        }
+    }
+    lines.push(format!("{}--device", ARG_PREFIX));
+    lines.push(format!("{}{}", ARG_PREFIX, options.device));
+    if let Some(seed) = options.train_seed {
+        lines.push(format!("{}--train-seed", ARG_PREFIX));
+        lines.push(format!("{}{}", ARG_PREFIX, seed));
+    }
+    lines.push("# effective options".to_string());
+    lines.extend(format!("{:#?}", options).lines().map(|line| format!("# {}", line)));
+
+    let text = lines.join("\n") + "\n";
+    if path == STDOUT_PATH {
+        print!("{}", text);
+        return Ok(());
+    }
+
+    fs::write(path, text).map_err(|e| VibeError::new(format!("unable to write manifest {}: {}", path, e)))
+}
+
+// Read back the flags of a manifest, in the order they were given.
+pub fn read_flags(path: &str) -> Result<Vec<String>, VibeError> {
+    let content = fs::read_to_string(path).map_err(|e| VibeError::new(format!("unable to open manifest {}: {}", path, e)))?;
+
+    Ok(content
+        .lines()
+        .filter_map(|line| line.strip_prefix(ARG_PREFIX))
+        .map(String::from)
+        .collect())
+}
@@ -1,34 +1,195 @@
 use crate::error::VibeError;
 
-use crossterm::event::KeyEvent;
+use crossterm::event::{KeyEvent, MouseEvent};
 use std::sync::mpsc::{self, Receiver, Sender};
 
 #[derive(Debug, Clone)]
 pub enum LossType {
     Training,
     Validation,
+    Evaluation,
+}
+
+// The model's prediction of one character of a scored word.
+#[derive(Debug, Clone)]
+pub struct ScoreStep {
+    pub context: String,
+    pub token: String,
+    pub probability: f32,
+}
+
+// One step of generating a word, the distribution the next token is sampled from.
+#[derive(Debug, Clone)]
+pub struct GenerationStep {
+    pub context: String,
+    pub output: String,
+    pub candidates: Vec<(String, f32)>,
+}
+
+// One stage of the network, for drawing the architecture.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub size: String,
+    pub parameters: usize,
+}
+
+// The average loss of predicting the token at one position within words.
+#[derive(Debug, Clone, Copy)]
+pub struct PositionLoss {
+    pub loss: f32,
+    pub count: usize,
+}
+
+// The spread of a parameter's values, how many fall in each of the equal width buckets from min to max.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub name: String,
+    pub min: f32,
+    pub max: f32,
+    pub counts: Vec<usize>,
+}
+
+// Statistics about a batch of generated words.
+#[derive(Debug, Clone, Copy)]
+pub struct GenerationSummary {
+    pub unique: usize,
+    pub count: usize,
+    // Words not found in the training set, unknown without training data.
+    pub novel: Option<usize>,
+    // Words the filter rejected and redrew.
+    pub rejected: usize,
+    // Words with a banned substring that were redrawn, or dropped when redrawing didn't help.
+    pub banned: usize,
+    // Average per-character probability of the novel words, the geometric mean over each word.
+    pub likelihood: f32,
+}
+
+impl GenerationSummary {
+    // The fraction of novel words scaled by how likely the model found them, a single number to compare
+    // models by.
+    pub fn quality(&self) -> Option<f32> {
+        self.novel.map(|novel| novel as f32 / self.count.max(1) as f32 * self.likelihood)
+    }
 }
 
 // Message types for communication between training thread and UI.
 #[derive(Debug, Clone)]
 pub enum ModelResultMessage {
-    Progress { loss_type: LossType, iteration: usize, loss: f32 },
-    Generated { text: String },
-    Error { err: VibeError },
+    Progress {
+        run: usize,
+        loss_type: LossType,
+        iteration: usize,
+        loss: f32,
+    },
+    Generated {
+        text: String,
+        tag: Option<String>,
+        // The probability of each character from the start of the text, appended text has none.
+        confidence: Vec<f32>,
+        // The closest training word and its edit distance, when there's training data and one is close.
+        nearest: Option<(String, usize)>,
+    },
+    GenerationSummary {
+        summary: GenerationSummary,
+    },
+    GenerationStep {
+        step: GenerationStep,
+    },
+    Description {
+        text: String,
+        layers: Vec<Layer>,
+    },
+    Scored {
+        word: String,
+        steps: Vec<ScoreStep>,
+    },
+    PositionLosses {
+        losses: Vec<PositionLoss>,
+    },
+    Probed {
+        context: String,
+        distribution: Vec<(String, f32)>,
+    },
+    LossFloor {
+        loss: f32,
+    },
+    // The batch size a training run uses, smaller than asked for when it was clamped to the data.
+    BatchSize {
+        run: usize,
+        size: usize,
+    },
+    // The fraction of hidden activations saturated on a training batch.
+    Saturation {
+        run: usize,
+        iteration: usize,
+        fraction: f32,
+    },
+    // A word sampled partway through training.
+    LiveSample {
+        run: usize,
+        iteration: usize,
+        text: String,
+    },
+    // The model picked up from an autosave made at this iteration.
+    Resumed {
+        run: usize,
+        iteration: usize,
+    },
+    // The model moved to another device.
+    DeviceSwitched {
+        run: usize,
+        name: String,
+    },
+    // Pretraining finished and fine-tuning on the training data starts at this iteration.
+    FineTune {
+        run: usize,
+        iteration: usize,
+    },
+    Histograms {
+        run: usize,
+        iteration: usize,
+        weights: Vec<Histogram>,
+        gradients: Vec<Histogram>,
+    },
+    Status {
+        run: usize,
+        text: String,
+    },
+    Error {
+        run: usize,
+        err: VibeError,
+    },
     Finished,
+    // The model is built and its data loaded, or it failed to build, sent once at startup.
+    Ready {
+        run: usize,
+        built: bool,
+    },
 }
 
 // Message types for sending commands to the model.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ModelCommandMessage {
-    Train { iterations: usize, start: usize },
+    // Pretraining iterations on the pretraining data come first, when there's any.
+    Train { iterations: usize, start: usize, pretrain: usize },
     Vibe { count: usize },
+    ReloadData,
+    Describe,
+    Score { word: String },
+    PositionLosses,
+    LossFloor,
+    Probe { context: String },
+    // Move the model and its data to another device, by name.
+    SwitchDevice { name: String },
     Save,
     Shutdown,
 }
 
 pub enum EventMessage {
     Key { event: KeyEvent },
+    Mouse { event: MouseEvent },
+    Resize { width: u16, height: u16 },
 }
 
 pub enum AppMessage {
@@ -1,4 +1,6 @@
 pub mod app;
 pub mod device;
+pub mod keys;
+pub mod manifest;
 pub mod message;
 pub mod options;
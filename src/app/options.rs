@@ -1,5 +1,18 @@
-use crate::{app::device, data::parse, error::VibeError, model};
-use std::env;
+use crate::{
+    app::{device, keys::KeyBindings, manifest},
+    data::{
+        convert, distance,
+        output::OutputFormat,
+        parse::{self, Encoding},
+        tokenize::{Padding, Tokenizer},
+    },
+    error::VibeError,
+    logger::Level,
+    model, sampler,
+    ui::colors::ColorMode,
+};
+use regex::Regex;
+use std::{env, fmt, path::Path, str::FromStr, time::Duration};
 
 const DEFAULT_DATA_PATH: &str = parse::DEFAULT_DATA_PATH;
 const DEFAULT_MODEL_PATH: &str = model::DEFAULT_MODEL_PATH;
@@ -10,6 +23,174 @@ const DEFAULT_EMBEDDING_SIZE: usize = 5;
 const DEFAULT_HIDDEN_SIZE: usize = 1000;
 const DEFAULT_LEARN_RATE: f32 = 0.1;
 const DEFAULT_GENERATE: usize = 20;
+const DEFAULT_MAX_MEMORY: usize = 2048;
+const DEFAULT_PROGRESS_INTERVAL: usize = 1;
+const DEFAULT_PATIENCE: usize = 0;
+const DEFAULT_AUTOSAVE_EVERY: usize = 0;
+const DEFAULT_TARGET_LENGTH: usize = 0;
+const DEFAULT_LENGTH_STRENGTH: f32 = 0.5;
+const DEFAULT_FREQUENCY_CAP: usize = 0;
+const DEFAULT_VISUALIZE_DELAY: u64 = 250;
+const DEFAULT_EMA_DECAY: f32 = 0.;
+const DEFAULT_LABEL_SMOOTHING: f32 = 0.;
+const DEFAULT_GENERATE_PARALLEL: usize = 1;
+const DEFAULT_CHART_WINDOW: usize = 0;
+const DEFAULT_DISTILL_CAP: f32 = 0.25;
+const DEFAULT_DEBUG_HISTOGRAMS: usize = 0;
+const DEFAULT_RESTARTS: usize = 0;
+const DEFAULT_SMOOTHING: usize = 1;
+const DEFAULT_PRETRAIN_ITERATIONS: usize = 1000;
+const DEFAULT_SAMPLE_EVERY: usize = 0;
+const DEFAULT_DEBUG_SATURATION: usize = 0;
+const DEFAULT_ENUMERATE_TOP: usize = 0;
+const DEFAULT_VAL_WARMUP: usize = 0;
+const DEFAULT_SAMPLER: &str = sampler::DEFAULT_SAMPLER;
+
+// The unit losses are reported in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LossUnits {
+    Nats,
+    Bits,
+}
+
+impl LossUnits {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().as_str() {
+            "nats" => Ok(Self::Nats),
+            "bits" => Ok(Self::Bits),
+            _ => Err(VibeError::new(format!("invalid units {}, expected nats or bits", name))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Nats => "nats",
+            Self::Bits => "bits",
+        }
+    }
+
+    // The factor converting a natural log cross-entropy loss into this unit.
+    pub fn scale(&self) -> f32 {
+        match self {
+            Self::Nats => 1.,
+            Self::Bits => 1. / std::f32::consts::LN_2,
+        }
+    }
+}
+
+// How the embedding table starts out when there's no saved model to load.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmbeddingInit {
+    Random,
+    Cooccurrence,
+}
+
+impl EmbeddingInit {
+    // Parse a `layer=method` initialization, only the embeddings have a choice.
+    pub fn from_spec(spec: &str) -> Result<Self, VibeError> {
+        match spec.split_once('=').map(|(layer, method)| (layer.trim(), method.trim())) {
+            Some(("embeddings", "random")) => Ok(Self::Random),
+            Some(("embeddings", "cooccurrence")) => Ok(Self::Cooccurrence),
+            _ => Err(VibeError::new(format!(
+                "invalid initialization {}, expected embeddings=random or embeddings=cooccurrence",
+                spec
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Random => "random",
+            Self::Cooccurrence => "cooccurrence",
+        }
+    }
+}
+
+// The distribution the random parameters are drawn from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InitDistribution {
+    Uniform,
+    Normal,
+}
+
+impl InitDistribution {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().as_str() {
+            "uniform" => Ok(Self::Uniform),
+            "normal" => Ok(Self::Normal),
+            _ => Err(VibeError::new(format!(
+                "invalid init distribution {}, expected uniform or normal",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Uniform => "uniform",
+            Self::Normal => "normal",
+        }
+    }
+}
+
+// How much each training example's loss counts by the position of its target within the word.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PositionWeights {
+    // Every position counts the same.
+    Uniform,
+    // Ramps up over the positions with padding in their context, full weight from the first without any.
+    Linear,
+}
+
+impl PositionWeights {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().as_str() {
+            "uniform" => Ok(Self::Uniform),
+            "linear" => Ok(Self::Linear),
+            _ => Err(VibeError::new(format!(
+                "invalid position weights {}, expected uniform or linear",
+                name
+            ))),
+        }
+    }
+}
+
+// Layers left untouched by backpropagation, e.g. to fine-tune only the output layer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrozenLayers {
+    pub embeddings: bool,
+    pub hidden: bool,
+    pub output: bool,
+}
+
+impl FrozenLayers {
+    // Parse a comma separated list of layer names.
+    pub fn from_names(names: &str) -> Result<Self, VibeError> {
+        let mut frozen = Self::default();
+        for name in names.split(',').map(|name| name.trim()) {
+            match name {
+                "embeddings" => frozen.embeddings = true,
+                "hidden" => frozen.hidden = true,
+                "output" => frozen.output = true,
+                _ => {
+                    return Err(VibeError::new(format!(
+                        "invalid layer {}, expected embeddings, hidden or output",
+                        name
+                    )));
+                }
+            }
+        }
+
+        Ok(frozen)
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        [(self.embeddings, "embeddings"), (self.hidden, "hidden"), (self.output, "output")]
+            .into_iter()
+            .filter_map(|(frozen, name)| frozen.then_some(name))
+            .collect()
+    }
+}
 
 // User provided runtime arguments.
 #[derive(Debug, Clone)]
@@ -24,6 +205,91 @@ pub struct Options {
     pub hidden_size: usize,
     pub learn_rate: f32,
     pub generate: usize,
+    pub max_memory: usize,
+    pub baseline: Option<String>,
+    pub progress_interval: usize,
+    pub loss_log: Option<String>,
+    pub patience: usize,
+    pub alt_screen: bool,
+    pub sampler: String,
+    pub metrics_file: Option<String>,
+    pub key_bindings: KeyBindings,
+    pub prev_onehot: bool,
+    pub end_token: bool,
+    pub units: LossUnits,
+    pub autosave_every: usize,
+    pub score: Option<String>,
+    pub score_context: bool,
+    pub probe: Option<String>,
+    pub target_length: usize,
+    pub length_strength: f32,
+    pub start_weights: Vec<(String, f32)>,
+    pub freeze: FrozenLayers,
+    pub sample_checkpoints: bool,
+    pub frequency_cap: usize,
+    pub help: bool,
+    pub print_vocab: bool,
+    pub verify_tokenization: bool,
+    pub visualize_generation: bool,
+    pub visualize_delay: u64,
+    pub prefix: String,
+    pub loss_fd: Option<String>,
+    pub tokenizer: Tokenizer,
+    pub padding: Padding,
+    pub max_time: Option<Duration>,
+    pub resume: bool,
+    pub sample_every: usize,
+    pub debug_saturation: usize,
+    pub export_json: Option<String>,
+    pub filter_regex: Option<Regex>,
+    pub ban_list: Vec<String>,
+    pub enumerate_top: usize,
+    pub eval_file: Option<String>,
+    pub color_mode: ColorMode,
+    pub suffix: String,
+    pub ema_decay: f32,
+    pub generate_ema: bool,
+    pub log_file: Option<String>,
+    pub log_level: Level,
+    pub hard_mining: bool,
+    pub generate_seed: Option<u64>,
+    // Seeds the data shuffle and training batches, picked at random once the flags are parsed when not given.
+    pub train_seed: Option<u64>,
+    pub clamp_batch: bool,
+    pub label_smoothing: f32,
+    pub balance_starts: bool,
+    pub no_validation: bool,
+    pub generate_parallel: usize,
+    pub embedding_init: EmbeddingInit,
+    pub split_on: Option<char>,
+    pub chart_window: usize,
+    pub autostart: bool,
+    pub exit_on_finish: bool,
+    pub distill: Option<f32>,
+    pub distill_cap: f32,
+    pub debug_histograms: usize,
+    pub capitalize: bool,
+    pub suffix_append: String,
+    pub self_test: bool,
+    pub init_distribution: InitDistribution,
+    pub encoding: Encoding,
+    pub restarts: usize,
+    pub mouse: bool,
+    pub smoothing: usize,
+    pub output: Option<String>,
+    pub output_format: OutputFormat,
+    pub pretrain_data: Option<String>,
+    pub pretrain_iterations: usize,
+    pub compare_flags: Option<String>,
+    pub compare: Option<Box<Options>>,
+    // Combinations that are allowed but probably not what was meant, shown once the model is ready.
+    pub warnings: Vec<String>,
+    pub manifest: Option<String>,
+    pub val_warmup: usize,
+    pub prune_vocab: bool,
+    pub position_weights: PositionWeights,
+    // The flags the options were parsed from with any manifests replayed, for writing a manifest.
+    pub args: Vec<String>,
 }
 
 impl Options {
@@ -39,126 +305,1065 @@ impl Options {
             hidden_size: DEFAULT_HIDDEN_SIZE,
             learn_rate: DEFAULT_LEARN_RATE,
             generate: DEFAULT_GENERATE,
+            max_memory: DEFAULT_MAX_MEMORY,
+            baseline: None,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            loss_log: None,
+            patience: DEFAULT_PATIENCE,
+            alt_screen: true,
+            sampler: DEFAULT_SAMPLER.to_string(),
+            metrics_file: None,
+            key_bindings: KeyBindings::new(),
+            prev_onehot: false,
+            end_token: false,
+            units: LossUnits::Nats,
+            autosave_every: DEFAULT_AUTOSAVE_EVERY,
+            score: None,
+            score_context: false,
+            probe: None,
+            target_length: DEFAULT_TARGET_LENGTH,
+            length_strength: DEFAULT_LENGTH_STRENGTH,
+            start_weights: Vec::new(),
+            freeze: FrozenLayers::default(),
+            sample_checkpoints: false,
+            frequency_cap: DEFAULT_FREQUENCY_CAP,
+            help: false,
+            print_vocab: false,
+            verify_tokenization: false,
+            visualize_generation: false,
+            visualize_delay: DEFAULT_VISUALIZE_DELAY,
+            prefix: String::new(),
+            loss_fd: None,
+            tokenizer: Tokenizer::Char,
+            padding: Padding::Delimiter,
+            max_time: None,
+            resume: false,
+            sample_every: DEFAULT_SAMPLE_EVERY,
+            debug_saturation: DEFAULT_DEBUG_SATURATION,
+            export_json: None,
+            filter_regex: None,
+            ban_list: Vec::new(),
+            enumerate_top: DEFAULT_ENUMERATE_TOP,
+            eval_file: None,
+            color_mode: ColorMode::Auto,
+            suffix: String::new(),
+            ema_decay: DEFAULT_EMA_DECAY,
+            generate_ema: false,
+            log_file: None,
+            log_level: Level::Info,
+            hard_mining: false,
+            generate_seed: None,
+            train_seed: None,
+            clamp_batch: false,
+            label_smoothing: DEFAULT_LABEL_SMOOTHING,
+            balance_starts: false,
+            no_validation: false,
+            generate_parallel: DEFAULT_GENERATE_PARALLEL,
+            embedding_init: EmbeddingInit::Random,
+            split_on: None,
+            chart_window: DEFAULT_CHART_WINDOW,
+            autostart: false,
+            exit_on_finish: false,
+            distill: None,
+            distill_cap: DEFAULT_DISTILL_CAP,
+            debug_histograms: DEFAULT_DEBUG_HISTOGRAMS,
+            capitalize: false,
+            suffix_append: String::new(),
+            self_test: false,
+            init_distribution: InitDistribution::Uniform,
+            encoding: Encoding::Utf8,
+            restarts: DEFAULT_RESTARTS,
+            mouse: false,
+            smoothing: DEFAULT_SMOOTHING,
+            output: None,
+            output_format: OutputFormat::Text,
+            pretrain_data: None,
+            pretrain_iterations: DEFAULT_PRETRAIN_ITERATIONS,
+            compare_flags: None,
+            compare: None,
+            warnings: Vec::new(),
+            manifest: None,
+            val_warmup: DEFAULT_VAL_WARMUP,
+            prune_vocab: false,
+            position_weights: PositionWeights::Uniform,
+            args: Vec::new(),
         }
     }
 }
 
+// Environment variables read as a fallback for the flag they're paired with.
+const ENV_FLAGS: [(&str, &str); 16] = [
+    ("GPTURD_DATA", "--data"),
+    ("GPTURD_MODEL", "--model"),
+    ("GPTURD_DEVICE", "--device"),
+    ("GPTURD_ITERATIONS", "--iterations"),
+    ("GPTURD_BATCH_SIZE", "--batch-size"),
+    ("GPTURD_BLOCK_SIZE", "--block-size"),
+    ("GPTURD_EMBEDDING_SIZE", "--embedding-size"),
+    ("GPTURD_HIDDEN_SIZE", "--hidden-size"),
+    ("GPTURD_LEARN_RATE", "--learn-rate"),
+    ("GPTURD_GENERATE", "--generate"),
+    ("GPTURD_MAX_MEMORY", "--max-memory"),
+    ("GPTURD_SAMPLER", "--sampler"),
+    ("GPTURD_TOKENIZER", "--tokenizer"),
+    ("GPTURD_COLOR_MODE", "--color-mode"),
+    ("GPTURD_LOG_FILE", "--log-file"),
+    ("GPTURD_LOG_LEVEL", "--log-level"),
+];
+
+// Single letter forms of the most used flags.
+const SHORT_FLAGS: [(&str, &str); 11] = [
+    ("-h", "--help"),
+    ("-D", "--data"),
+    ("-m", "--model"),
+    ("-d", "--device"),
+    ("-i", "--iterations"),
+    ("-b", "--batch-size"),
+    ("-l", "--learn-rate"),
+    ("-g", "--generate"),
+    ("-s", "--sampler"),
+    ("-p", "--prefix"),
+    ("-o", "--output"),
+];
+
+// Every flag in the order the help lists them, with the placeholder for its value when it takes one and its
+// help, the default or what it does.
+const FLAGS: [(&str, Option<&str>, &str); 91] = [
+    ("--help", None, "print this help then exit"),
+    ("--data", Some("data path"), "data/names_short.txt"),
+    ("--model", Some("model path"), "model.safetensors"),
+    ("--device", Some("auto|cpu|cuda|metal|gpu"), "auto, also nvidia and mps"),
+    ("--iterations", Some("num"), "1000"),
+    ("--batch-size", Some("num"), "512"),
+    ("--block-size", Some("num"), "3"),
+    ("--embedding-size", Some("num"), "5"),
+    ("--hidden-size", Some("num"), "1000"),
+    ("--learn-rate", Some("rate"), "0.1"),
+    ("--generate", Some("num"), "20"),
+    ("--max-memory", Some("MiB"), "2048"),
+    ("--baseline", Some("loss csv path"), "none"),
+    ("--progress-interval", Some("num"), "1"),
+    ("--loss-log", Some("loss csv path"), "none"),
+    ("--patience", Some("num"), "0, off"),
+    ("--no-alt-screen", None, "render inline, keeping scrollback"),
+    (
+        "--sampler",
+        Some("multinomial|greedy|top-k:<k>|top-p:<p>|temperature:<t>"),
+        "multinomial",
+    ),
+    ("--metrics-file", Some("json path"), "none"),
+    ("--keys", Some("bindings path"), "none"),
+    (
+        "--bind",
+        Some("action=key,..."),
+        "train=t,enter vibe=v stream=m cancel=x reload=r popup=p grid=g pin=n diff=d score=c probe=o positions=l histograms=h floor=f smooth=+,= unsmooth=- info=i device=w save=s quit=q,esc",
+    ),
+    ("--add-prev-onehot", None, "feed the last character's one-hot to the hidden layer"),
+    ("--end-token", None, "use separate start and end of word tokens"),
+    ("--units", Some("nats|bits"), "nats"),
+    ("--autosave-every", Some("num"), "0, off"),
+    ("--score", Some("word"), "none"),
+    ("--score-context", None, "show the context window of each scored character"),
+    ("--probe", Some("context"), "rank the next token after this context, none"),
+    ("--target-length", Some("num"), "0, off"),
+    ("--length-strength", Some("strength"), "0.5"),
+    ("--freeze", Some("embeddings,hidden,output"), "none"),
+    ("--sample-checkpoints", None, "generate a sample before and after training"),
+    ("--frequency-cap", Some("num"), "0, off"),
+    ("--print-vocab", None, "print the vocabulary and character counts then exit"),
+    (
+        "--verify-tokenization",
+        None,
+        "check the data survives tokenizing and rebuilding then exit",
+    ),
+    (
+        "--visualize-generation",
+        None,
+        "show the next character distribution while generating",
+    ),
+    ("--visualize-delay", Some("ms"), "250"),
+    (
+        "--prefix",
+        Some("text"),
+        "start generated words with text, falling back to shorter prefixes",
+    ),
+    ("--loss-fd", Some("fd or path"), "stream losses as TSV lines, none"),
+    ("--tokenizer", Some("name"), "char, char|syllable"),
+    ("--export-json", Some("json path"), "write the saved model as JSON then exit"),
+    ("--filter-regex", Some("pattern"), "redraw generated words that don't match, none"),
+    (
+        "--eval-file",
+        Some("data path"),
+        "track the loss on another data file while training, none",
+    ),
+    ("--color-mode", Some("mode"), "auto, auto|truecolor|256|16"),
+    ("--suffix", Some("text"), "train and generate in reverse to end words with text"),
+    ("--ema-decay", Some("decay"), "0, off, e.g. 0.999"),
+    ("--generate-ema", None, "generate from the averaged weights"),
+    ("--log-file", Some("log path"), "append events to a log file, none"),
+    ("--log-level", Some("level"), "info, error|warn|info|debug"),
+    ("--hard-mining", None, "sample training batches weighted by each example's loss"),
+    (
+        "--generate-seed",
+        Some("seed"),
+        "seed each generated word for a reproducible list, none",
+    ),
+    ("--train-seed", Some("seed"), "seed the data shuffle and training batches, random"),
+    (
+        "--clamp-batch",
+        None,
+        "shrink a batch size larger than the training set instead of erroring",
+    ),
+    ("--balance-starts", None, "oversample word starts with rare first letters"),
+    ("--split-on", Some("char"), "also break words on this character, e.g. \",\", none"),
+    (
+        "--init",
+        Some("layer=method"),
+        "embeddings=random, or embeddings=cooccurrence for a bigram SVD start",
+    ),
+    (
+        "--generate-parallel",
+        Some("count"),
+        "1, how many words are sampled together in one batched pass",
+    ),
+    (
+        "--no-validation",
+        None,
+        "train on all of the data without holding out a validation set",
+    ),
+    (
+        "--compare",
+        Some("flags"),
+        "train a second model with these flags changed, e.g. \"--learn-rate 0.01\", quote values with spaces",
+    ),
+    ("--label-smoothing", Some("weight"), "0, off, e.g. 0.1"),
+    ("--chart-window", Some("num"), "0, all, chart only the most recent training points"),
+    ("--autostart", None, "start training on launch without waiting for a key"),
+    (
+        "--exit-on-finish",
+        None,
+        "quit once training or generating finishes, failing on an error",
+    ),
+    (
+        "--distill",
+        Some("likelihood"),
+        "add novel generated words at least this likely to the training data, none",
+    ),
+    (
+        "--distill-cap",
+        Some("fraction"),
+        "0.25, most generated words added as a fraction of the real ones",
+    ),
+    (
+        "--debug-histograms",
+        Some("interval"),
+        "0, off, histogram the weights and gradients every interval iterations",
+    ),
+    ("--capitalize", None, "capitalize the first letter of generated words"),
+    ("--suffix-append", Some("text"), "append text to generated words as is, none"),
+    (
+        "--self-test",
+        None,
+        "train a small model on built-in words to check the device works, then exit",
+    ),
+    (
+        "--init-dist",
+        Some("uniform|normal"),
+        "uniform, what the random parameters are drawn from",
+    ),
+    ("--encoding", Some("utf-8|latin-1|lossy"), "utf-8, how data files are decoded"),
+    (
+        "--restarts",
+        Some("num"),
+        "0, off, train this many fresh models without the interface and save the best",
+    ),
+    ("--mouse", None, "click keybindings to use them and scroll the vibes with the wheel"),
+    (
+        "--smoothing",
+        Some("window"),
+        "1, off, average the charted training loss over this many points",
+    ),
+    ("--output", Some("path"), "write each batch of generated words to a file, none"),
+    (
+        "--output-format",
+        Some("text|csv|json"),
+        "text, csv and json include the likelihood and novelty",
+    ),
+    (
+        "--pretrain-data",
+        Some("data path"),
+        "train on this data first, then fine-tune on --data, none",
+    ),
+    (
+        "--pretrain-iterations",
+        Some("num"),
+        "1000, iterations on the --pretrain-data before fine-tuning",
+    ),
+    (
+        "--start-weights",
+        Some("token=weight,..."),
+        "scale how likely words start with a token when there's no prefix, none",
+    ),
+    (
+        "--padding",
+        Some("delimiter|repeat-first|none"),
+        "delimiter, what fills the context before a word's first tokens",
+    ),
+    (
+        "--max-time",
+        Some("time"),
+        "stop training after this long, e.g. 90s, 5m or 1.5h, none",
+    ),
+    ("--resume", None, "continue training from the newest autosave of the model file"),
+    (
+        "--sample-every",
+        Some("interval"),
+        "0, off, show a freshly generated word every interval iterations while training",
+    ),
+    (
+        "--debug-saturation",
+        Some("interval"),
+        "0, off, measure how many hidden activations are saturated every interval iterations",
+    ),
+    (
+        "--ban-list",
+        Some("path"),
+        "redraw generated words containing a listed substring, none",
+    ),
+    (
+        "--enumerate-top",
+        Some("count"),
+        "0, off, print the most probable complete words of the saved model then exit",
+    ),
+    (
+        "--manifest",
+        Some("path"),
+        "write the flags and setup of the run to the path, - for stdout, none",
+    ),
+    (
+        "--from-manifest",
+        Some("path"),
+        "replay the flags of a manifest, later flags override them",
+    ),
+    ("--val-warmup", Some("iterations"), "0, skip validation before this iteration"),
+    (
+        "--prune-vocab",
+        None,
+        "drop letters the training data never uses from the vocabulary",
+    ),
+    (
+        "--position-weights",
+        Some("uniform|linear"),
+        "uniform, linear counts the padded early positions less in the loss",
+    ),
+];
+
+// How far off an unrecognized flag can be from a real one and still be suggested.
+const SUGGESTION_DISTANCE: usize = 3;
+
 // Parse the command line options.
+//
+// NOTE: set environment variables are turned into their flags ahead of the command line ones, so they're
+// validated the same way and any flag given on the command line takes precedence over them.
 pub fn parse_args(options: &mut Options) -> Result<(), VibeError> {
-    let mut args: Vec<String> = env::args().collect();
-    args.reverse();
-    args.pop();
+    let args: Vec<String> = env_args().into_iter().chain(env::args().skip(1)).collect();
+    let args = replay_manifests(args)?;
+    options.args = args.clone();
+    parse_flags(options, args)?;
 
-    while let Some(arg) = args.pop() {
-        match arg.as_str() {
-            "--data" => {
-                if let Some(path) = args.pop() {
-                    options.data = path;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the path portion of the --data flag"));
-                }
+    // An unseeded run picks its seed now so the manifest can record it, and a comparison run shares it.
+    options.train_seed.get_or_insert_with(rand::random);
+
+    parse_compare(options)
+}
+
+// The comparison run starts from the same options with its own flags applied on top.
+fn parse_compare(options: &mut Options) -> Result<(), VibeError> {
+    if let Some(flags) = &options.compare_flags {
+        let mut compare = options.clone();
+        compare.compare_flags = None;
+        compare.warnings.clear();
+        parse_flags(&mut compare, replay_manifests(split_flags(flags)?)?)?;
+        if compare.compare_flags.is_some() {
+            return Err(VibeError::new("--compare can't be used inside the --compare flags"));
+        }
+
+        separate_outputs(&mut compare, options);
+        options.compare = Some(Box::new(compare));
+    }
+
+    Ok(())
+}
+
+// Split the --compare flags into arguments the way a shell would, on whitespace outside of single or double
+// quotes, so a value can have spaces in it.
+fn split_flags(text: &str) -> Result<Vec<String>, VibeError> {
+    let mut args: Vec<String> = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut quote: Option<char> = None;
+
+    for letter in text.chars() {
+        match (quote, letter) {
+            (Some(open), _) if letter == open => quote = None,
+            (Some(_), _) => arg.get_or_insert_default().push(letter),
+            (None, '\'' | '"') => {
+                quote = Some(letter);
+                arg.get_or_insert_default();
             }
-            "--model" => {
-                if let Some(path) = args.pop() {
-                    options.model_file = path;
-                } else {
+            (None, _) if letter.is_whitespace() => args.extend(arg.take()),
+            (None, _) => arg.get_or_insert_default().push(letter),
+        }
+    }
+    if let Some(open) = quote {
+        return Err(VibeError::new(format!("unclosed {} quote in the --compare flags: {}", open, text)));
+    }
+    args.extend(arg);
+
+    Ok(args)
+}
+
+// Swap each --from-manifest for the flags recorded in the manifest, in place so later flags still override them.
+fn replay_manifests(args: Vec<String>) -> Result<Vec<String>, VibeError> {
+    let mut replayed: Vec<String> = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let path = match arg.split_once('=') {
+            Some(("--from-manifest", path)) => path.to_string(),
+            _ if arg == "--from-manifest" => match args.next() {
+                Some(path) if !is_flag(&path) => path,
+                _ => {
                     print_help();
-                    return Err(VibeError::new("missing the path portion of the --model flag"));
+                    return Err(VibeError::new("missing the path portion of the --from-manifest flag"));
                 }
+            },
+            _ => {
+                replayed.push(arg);
+                continue;
             }
-            "--device" => {
-                if let Some(path) = args.pop() {
-                    options.device = path;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the device portion of the --device flag"));
-                }
+        };
+        replayed.extend(manifest::read_flags(&path)?);
+    }
+
+    Ok(replayed)
+}
+
+// Apply a list of flags to the options, then check the combination.
+//
+// Short flags are swapped for their long form and a `--flag=value` is split in two, then each flag is looked
+// up in the flag table to know whether it takes a value before it's applied.
+fn parse_flags(options: &mut Options, mut args: Vec<String>) -> Result<(), VibeError> {
+    args.reverse();
+
+    while let Some(arg) = args.pop() {
+        let arg = match SHORT_FLAGS.iter().find(|(short, _)| *short == arg) {
+            Some((_, long)) => long.to_string(),
+            None => arg,
+        };
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let Some(&(flag, placeholder, _)) = FLAGS.iter().find(|(flag, _, _)| *flag == name) else {
+            print_help();
+            return Err(VibeError::new(match suggest_flag(name) {
+                Some(flag) => format!("unrecognized argument: {}, did you mean {}?", name, flag),
+                None => format!("unrecognized argument: {}", name),
+            }));
+        };
+
+        let value = match (placeholder, inline_value) {
+            (None, Some(_)) => {
+                return Err(VibeError::new(format!(
+                    "{} is a switch and doesn't take a value, give it on its own instead of {}",
+                    flag, arg
+                )));
             }
-            "--iterations" => {
-                if let Some(iterations) = args.pop() {
-                    options.iterations = str::parse::<usize>(iterations.as_str())?;
-                } else {
+            (None, None) => String::new(),
+            (Some(_), Some(value)) => value,
+            (Some(placeholder), None) => match args.pop() {
+                Some(value) if !is_flag(&value) => value,
+                next => {
                     print_help();
-                    return Err(VibeError::new("missing the number portion of the --iterations flag"));
+                    return Err(VibeError::new(match next {
+                        Some(next) => format!(
+                            "missing the <{}> portion of the {} flag, {} is taken for the next flag, give it as {}={} if it's the value",
+                            placeholder, flag, next, flag, next
+                        ),
+                        None => format!("missing the <{}> portion of the {} flag", placeholder, flag),
+                    }));
                 }
+            },
+        };
+        apply_flag(options, flag, value.clone())
+            .map_err(|err| VibeError::new(format!("invalid value '{}' for the {} flag: {}", value, flag, err.message())))?;
+    }
+
+    if !options.prefix.is_empty() && !options.suffix.is_empty() {
+        return Err(VibeError::new("--prefix and --suffix can't be used together"));
+    }
+    if options.no_validation && options.restarts > 0 {
+        return Err(VibeError::new(
+            "--restarts picks the best model by validation loss, it can't be used with --no-validation",
+        ));
+    }
+    if options.no_validation && options.patience > 0 {
+        return Err(VibeError::new(
+            "--patience needs the validation loss, it can't be used with --no-validation",
+        ));
+    }
+    if options.generate_ema && options.ema_decay == 0. {
+        return Err(VibeError::new("--generate-ema needs an --ema-decay to average the weights with"));
+    }
+
+    if options.iterations == 0 {
+        options.warnings.push("--iterations is 0, nothing will be trained".to_string());
+    }
+    if options.val_warmup > 0 && options.val_warmup >= options.iterations && !options.no_validation {
+        options
+            .warnings
+            .push("--val-warmup is past --iterations, the first run won't report a validation loss".to_string());
+    }
+    if options.position_weights == PositionWeights::Linear && options.padding == Padding::None {
+        options
+            .warnings
+            .push("--position-weights linear has no effect with --padding none, no context has padding".to_string());
+    }
+
+    // A vocabulary built from the data is only known once the model reads it, that's checked when it's built.
+    if options.tokenizer == Tokenizer::Char && !options.prune_vocab {
+        check_memory(options, convert::vocab_size(options.end_token))?;
+    }
+    options.key_bindings.validate()?;
+
+    Ok(())
+}
+
+// Set the option a flag from the flag table controls, switches are given an empty value.
+fn apply_flag(options: &mut Options, flag: &str, value: String) -> Result<(), VibeError> {
+    match flag {
+        "--help" => {
+            options.help = true;
+        }
+        "--data" => {
+            options.data = value;
+        }
+        "--model" => {
+            options.model_file = value;
+        }
+        "--device" => {
+            options.device = device::normalize(&value)?;
+        }
+        "--iterations" => {
+            options.iterations = parse_value::<usize>(&value)?;
+        }
+        "--batch-size" => {
+            options.batch_size = parse_value::<usize>(&value)?;
+        }
+        "--block-size" => {
+            options.block_size = parse_value::<usize>(&value)?;
+            if options.block_size == 0 {
+                return Err(VibeError::new("the block size must be at least 1, a context needs a token"));
             }
-            "--batch-size" => {
-                if let Some(size) = args.pop() {
-                    options.batch_size = str::parse::<usize>(size.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the size portion of the --batch-size flag"));
-                }
+        }
+        "--embedding-size" => {
+            options.embedding_size = parse_value::<usize>(&value)?;
+        }
+        "--hidden-size" => {
+            options.hidden_size = parse_value::<usize>(&value)?;
+        }
+        "--learn-rate" => {
+            options.learn_rate = parse_value::<f32>(&value)?;
+        }
+        "--generate" => {
+            options.generate = parse_value::<usize>(&value)?;
+        }
+        "--max-memory" => {
+            options.max_memory = parse_value::<usize>(&value)?;
+        }
+        "--baseline" => {
+            options.baseline = Some(value);
+        }
+        "--progress-interval" => {
+            options.progress_interval = parse_value::<usize>(&value)?.max(1);
+        }
+        "--loss-log" => {
+            options.loss_log = Some(value);
+        }
+        "--patience" => {
+            options.patience = parse_value::<usize>(&value)?;
+        }
+        "--no-alt-screen" => {
+            options.alt_screen = false;
+        }
+        "--sampler" => {
+            // Build the sampler once so bad names and parameters are reported up front.
+            sampler::from_name(&value)?;
+            options.sampler = value;
+        }
+        "--metrics-file" => {
+            options.metrics_file = Some(value);
+        }
+        "--keys" => {
+            options.key_bindings.load(&value)?;
+        }
+        "--bind" => {
+            options.key_bindings.bind(&value)?;
+        }
+        "--add-prev-onehot" => {
+            options.prev_onehot = true;
+        }
+        "--end-token" => {
+            options.end_token = true;
+        }
+        "--units" => {
+            options.units = LossUnits::from_name(&value)?;
+        }
+        "--autosave-every" => {
+            options.autosave_every = parse_value::<usize>(&value)?;
+        }
+        "--score" => {
+            options.score = Some(value);
+        }
+        "--probe" => {
+            options.probe = Some(value);
+        }
+        "--score-context" => {
+            options.score_context = true;
+        }
+        "--target-length" => {
+            options.target_length = parse_value::<usize>(&value)?;
+        }
+        "--length-strength" => {
+            options.length_strength = parse_value::<f32>(&value)?;
+        }
+        "--freeze" => {
+            options.freeze = FrozenLayers::from_names(&value)?;
+        }
+        "--sample-checkpoints" => {
+            options.sample_checkpoints = true;
+        }
+        "--frequency-cap" => {
+            options.frequency_cap = parse_value::<usize>(&value)?;
+        }
+        "--print-vocab" => {
+            options.print_vocab = true;
+        }
+        "--verify-tokenization" => {
+            options.verify_tokenization = true;
+        }
+        "--visualize-generation" => {
+            options.visualize_generation = true;
+        }
+        "--visualize-delay" => {
+            options.visualize_delay = parse_value::<u64>(&value)?;
+        }
+        "--prefix" => {
+            options.prefix = value;
+        }
+        "--loss-fd" => {
+            options.loss_fd = Some(value);
+        }
+        "--tokenizer" => {
+            options.tokenizer = Tokenizer::from_name(&value)?;
+        }
+        "--export-json" => {
+            options.export_json = Some(value);
+        }
+        "--filter-regex" => {
+            options.filter_regex = Some(model::parse_filter(&value)?);
+        }
+        "--eval-file" => {
+            options.eval_file = Some(value);
+        }
+        "--color-mode" => {
+            options.color_mode = ColorMode::from_name(&value)?;
+        }
+        "--suffix" => {
+            let suffix = value.trim().to_lowercase();
+            if let Some(letter) = suffix
+                .chars()
+                .find(|&letter| letter == '.' || convert::itol(convert::ltoi(letter)) != letter)
+            {
+                return Err(VibeError::new(format!(
+                    "the suffix {} has '{}' which isn't in the vocabulary",
+                    suffix, letter
+                )));
             }
-            "--block-size" => {
-                if let Some(size) = args.pop() {
-                    options.block_size = str::parse::<usize>(size.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the size portion of the --block-size flag"));
-                }
+            options.suffix = suffix;
+        }
+        "--ema-decay" => {
+            options.ema_decay = parse_value::<f32>(&value)?;
+            if !(0. ..1.).contains(&options.ema_decay) {
+                return Err(VibeError::new("the ema decay must be in [0, 1)"));
             }
-            "--embedding-size" => {
-                if let Some(size) = args.pop() {
-                    options.embedding_size = str::parse::<usize>(size.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the size portion of the --embedding-size flag"));
-                }
+        }
+        "--generate-ema" => {
+            options.generate_ema = true;
+        }
+        "--log-file" => {
+            options.log_file = Some(value);
+        }
+        "--log-level" => {
+            options.log_level = Level::from_name(&value)?;
+        }
+        "--hard-mining" => {
+            options.hard_mining = true;
+        }
+        "--generate-seed" => {
+            options.generate_seed = Some(parse_value::<u64>(&value)?);
+        }
+        "--train-seed" => {
+            options.train_seed = Some(parse_value::<u64>(&value)?);
+        }
+        "--clamp-batch" => {
+            options.clamp_batch = true;
+        }
+        "--balance-starts" => {
+            options.balance_starts = true;
+        }
+        "--split-on" => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(separator), None) => options.split_on = Some(separator),
+                _ => return Err(VibeError::new("expected a single character")),
             }
-            "--hidden-size" => {
-                if let Some(size) = args.pop() {
-                    options.hidden_size = str::parse::<usize>(size.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the size portion of the --hidden-size flag"));
-                }
+        }
+        "--init" => {
+            options.embedding_init = EmbeddingInit::from_spec(&value)?;
+        }
+        "--generate-parallel" => {
+            options.generate_parallel = parse_value::<usize>(&value)?;
+            if options.generate_parallel == 0 {
+                return Err(VibeError::new("the number of words generated in parallel must be at least 1"));
             }
-            "--learn-rate" => {
-                if let Some(rate) = args.pop() {
-                    options.learn_rate = str::parse::<f32>(rate.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the rate portion of the --learn-rate flag"));
-                }
+        }
+        "--chart-window" => {
+            options.chart_window = parse_value::<usize>(&value)?;
+        }
+        "--autostart" => {
+            options.autostart = true;
+        }
+        "--exit-on-finish" => {
+            options.exit_on_finish = true;
+        }
+        "--no-validation" => {
+            options.no_validation = true;
+        }
+        "--compare" => {
+            options.compare_flags = Some(value);
+        }
+        "--label-smoothing" => {
+            options.label_smoothing = parse_value::<f32>(&value)?;
+            if !(0. ..1.).contains(&options.label_smoothing) {
+                return Err(VibeError::new("the label smoothing must be in [0, 1)"));
             }
-            "--generate" => {
-                if let Some(count) = args.pop() {
-                    options.generate = str::parse::<usize>(count.as_str())?;
-                } else {
-                    print_help();
-                    return Err(VibeError::new("missing the number portion of the --generate flag"));
-                }
+        }
+        "--distill" => {
+            let threshold = parse_value::<f32>(&value)?;
+            if !(0. ..=1.).contains(&threshold) {
+                return Err(VibeError::new("the distill likelihood threshold must be in [0, 1]"));
             }
-            _ => {
-                print_help();
-                return Err(VibeError::new(format!("unrecognized argument: {}", arg)));
+            options.distill = Some(threshold);
+        }
+        "--distill-cap" => {
+            options.distill_cap = parse_value::<f32>(&value)?;
+            if options.distill_cap < 0. {
+                return Err(VibeError::new("the distill cap can't be negative"));
+            }
+        }
+        "--debug-histograms" => {
+            options.debug_histograms = parse_value::<usize>(&value)?;
+        }
+        "--capitalize" => {
+            options.capitalize = true;
+        }
+        "--suffix-append" => {
+            options.suffix_append = value;
+        }
+        "--self-test" => {
+            options.self_test = true;
+        }
+        "--init-dist" => {
+            options.init_distribution = InitDistribution::from_name(&value)?;
+        }
+        "--encoding" => {
+            options.encoding = Encoding::from_name(&value)?;
+        }
+        "--restarts" => {
+            options.restarts = parse_value::<usize>(&value)?;
+        }
+        "--mouse" => {
+            options.mouse = true;
+        }
+        "--smoothing" => {
+            options.smoothing = parse_value::<usize>(&value)?;
+            if options.smoothing == 0 {
+                return Err(VibeError::new("the smoothing window must be at least 1 point"));
             }
         }
+        "--output" => {
+            options.output = Some(value);
+        }
+        "--output-format" => {
+            options.output_format = OutputFormat::from_name(&value)?;
+        }
+        "--pretrain-data" => {
+            options.pretrain_data = Some(value);
+        }
+        "--pretrain-iterations" => {
+            options.pretrain_iterations = parse_value::<usize>(&value)?;
+        }
+        "--start-weights" => {
+            options.start_weights = model::parse_start_weights(&value)?;
+        }
+        "--padding" => {
+            options.padding = Padding::from_name(&value)?;
+        }
+        "--max-time" => {
+            options.max_time = Some(parse_duration(&value)?);
+        }
+        "--resume" => {
+            options.resume = true;
+        }
+        "--sample-every" => {
+            options.sample_every = parse_value::<usize>(&value)?;
+        }
+        "--debug-saturation" => {
+            options.debug_saturation = parse_value::<usize>(&value)?;
+        }
+        "--ban-list" => {
+            options.ban_list = model::read_ban_list(&value)?;
+        }
+        "--enumerate-top" => {
+            options.enumerate_top = parse_value::<usize>(&value)?;
+        }
+        "--manifest" => {
+            options.manifest = Some(value);
+        }
+        "--val-warmup" => {
+            options.val_warmup = parse_value::<usize>(&value)?;
+        }
+        "--prune-vocab" => {
+            options.prune_vocab = true;
+        }
+        "--position-weights" => {
+            options.position_weights = PositionWeights::from_name(&value)?;
+        }
+        // Replayed before the flags are applied, see `replay_manifests`.
+        "--from-manifest" => {}
+        _ => unreachable!("{} is in the flag table without being applied", flag),
     }
 
     Ok(())
 }
 
-// Print a usage help message.
-fn print_help() {
-    println!("usage:");
-    println!("command");
-    println!("\t--data           <data path>      ({})", DEFAULT_DATA_PATH);
-    println!("\t--model          <model path>     ({})", DEFAULT_MODEL_PATH);
-    println!(
-        "\t--device         <{}|{}|{}> ({})",
-        device::DEVICE_NAME_CPU,
-        device::DEVICE_NAME_CUDA,
-        device::DEVICE_NAME_METAL,
-        "auto",
-    );
-    println!("\t--iterations     <num>            ({})", DEFAULT_ITERATIONS);
-    println!("\t--batch-size     <num>            ({})", DEFAULT_BATCH_SIZE);
-    println!("\t--block-size     <num>            ({})", DEFAULT_BLOCK_SIZE);
-    println!("\t--embedding-size <num>            ({})", DEFAULT_EMBEDDING_SIZE);
-    println!("\t--hidden-size    <num>            ({})", DEFAULT_HIDDEN_SIZE);
-    println!("\t--learn-rate     <rate>           ({})", DEFAULT_LEARN_RATE);
-    println!("\t--generate       <num>            ({})", DEFAULT_GENERATE);
+// Keep a comparison run from writing over the files the main run writes, its model gets saved next to the
+// main one and its loss logs are dropped.
+fn separate_outputs(compare: &mut Options, options: &Options) {
+    if compare.model_file == options.model_file {
+        compare.model_file = Path::new(&options.model_file)
+            .with_extension("compare.safetensors")
+            .to_string_lossy()
+            .to_string();
+    }
+    if compare.loss_log == options.loss_log {
+        compare.loss_log = None;
+    }
+    if compare.metrics_file == options.metrics_file {
+        compare.metrics_file = None;
+    }
+}
+
+// Parse a flag's value, the flag is named in the error once it's applied.
+fn parse_value<T: FromStr>(value: &str) -> Result<T, VibeError>
+where
+    T::Err: fmt::Display,
+{
+    value.trim().parse::<T>().map_err(|err| VibeError::new(err.to_string()))
+}
+
+// Parse a length of time as a number with an optional s, m or h unit, seconds when there's none.
+fn parse_duration(text: &str) -> Result<Duration, VibeError> {
+    let text = text.trim();
+    let (number, scale) = match text.char_indices().last() {
+        Some((index, 's')) => (&text[..index], 1.),
+        Some((index, 'm')) => (&text[..index], 60.),
+        Some((index, 'h')) => (&text[..index], 3600.),
+        _ => (text, 1.),
+    };
+
+    let invalid = || VibeError::new(format!("invalid time {}, expected e.g. 90s, 5m or 1.5h", text));
+    let seconds = str::parse::<f64>(number).map_err(|_| invalid())? * scale;
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+// The flags for every set environment fallback.
+fn env_args() -> Vec<String> {
+    ENV_FLAGS
+        .iter()
+        .filter_map(|(name, flag)| match env::var(name) {
+            Ok(value) if !value.is_empty() => Some([flag.to_string(), value]),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+// Refuse model sizes whose parameters would not fit in the memory budget, before anything is allocated
+// on the device.
+pub fn check_memory(options: &Options, vocab_size: usize) -> Result<(), VibeError> {
+    let budget = options.max_memory.saturating_mul(1024 * 1024);
+
+    match model::estimate_memory(options, vocab_size) {
+        Some(required) if required <= budget => Ok(()),
+        Some(required) => Err(VibeError::new(format!(
+            "model needs about {} MiB for its parameters but --max-memory is {} MiB, reduce --embedding-size, --block-size or --hidden-size",
+            required.div_ceil(1024 * 1024),
+            options.max_memory
+        ))),
+        None => Err(VibeError::new(
+            "model size overflows, reduce --embedding-size, --block-size or --hidden-size",
+        )),
+    }
+}
+
+// The closest flag in the flag table to an unrecognized one, if any are close enough.
+fn suggest_flag(arg: &str) -> Option<&'static str> {
+    FLAGS
+        .iter()
+        .filter_map(|(flag, _, _)| distance::levenshtein(arg, flag, SUGGESTION_DISTANCE).map(|distance| (distance, *flag)))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, flag)| flag)
+}
+
+// Whether an argument reads as a flag rather than a value, several words starting with -- are still a value
+// so the --compare flags can be given in one argument.
+fn is_flag(arg: &str) -> bool {
+    arg.starts_with("--") && !arg.contains(char::is_whitespace)
+}
+
+// Every line of the help listing.
+fn help_lines() -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    lines.push("usage:".to_string());
+    lines.push("command".to_string());
+    for (flag, placeholder, help) in FLAGS {
+        let usage = match placeholder {
+            Some(placeholder) => format!("{:<16} <{}>", flag, placeholder),
+            None => flag.to_string(),
+        };
+        lines.push(format!("\t{:<33} ({})", usage, help));
+    }
+    lines.push("short flags".to_string());
+    for (short, long) in SHORT_FLAGS {
+        lines.push(format!("\t{:<22} {}", short, long));
+    }
+    lines.push("environment (overridden by the matching flag)".to_string());
+    for (name, flag) in ENV_FLAGS {
+        lines.push(format!("\t{:<22} {}", name, flag));
+    }
+
+    lines
+}
+
+// Print the help listing.
+pub fn print_help() {
+    for line in help_lines() {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, VibeError> {
+        let mut options = Options::new();
+        parse_flags(&mut options, args.iter().map(|arg| arg.to_string()).collect())?;
+        Ok(options)
+    }
+
+    #[test]
+    fn values_can_follow_an_equals_sign() {
+        let options = parse(&["--iterations=5", "--autostart"]).unwrap();
+        assert_eq!(options.iterations, 5);
+        assert!(options.autostart);
+    }
+
+    #[test]
+    fn switches_refuse_a_value() {
+        for arg in ["--autostart=true", "--autostart=false"] {
+            let err = parse(&[arg]).unwrap_err().to_string();
+            assert!(err.contains("--autostart is a switch"), "{}", err);
+            assert!(err.contains(arg), "{}", err);
+        }
+    }
+
+    #[test]
+    fn unrecognized_flags_suggest_the_closest() {
+        let err = parse(&["--iteratons=5"]).unwrap_err().to_string();
+        assert!(
+            err.contains("unrecognized argument: --iteratons, did you mean --iterations?"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn every_switch_in_the_table_is_applied() {
+        for (flag, _, _) in FLAGS.iter().filter(|(_, placeholder, _)| placeholder.is_none()) {
+            // Some switches need another flag to pass the checks, only an unknown flag would panic.
+            _ = parse(&[flag]);
+        }
+    }
+
+    #[test]
+    fn bad_values_name_their_flag() {
+        for args in [
+            ["--sampler", "top-k:abc"],
+            ["--bind", "bad"],
+            ["--device", "bogus"],
+            ["--units", "furlongs"],
+            ["--start-weights", "bad"],
+            ["--freeze", "bogus"],
+            ["--iterations", "many"],
+        ] {
+            let err = parse(&args).unwrap_err().to_string();
+            assert!(
+                err.contains(&format!("invalid value '{}' for the {} flag", args[1], args[0])),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn a_flag_missing_its_value_is_named() {
+        let err = parse(&["--data", "--iterations", "5"]).unwrap_err().to_string();
+        assert!(err.contains("missing the <data path> portion of the --data flag"), "{}", err);
+
+        let err = parse(&["--autostart", "--iterations"]).unwrap_err().to_string();
+        assert!(err.contains("missing the <num> portion of the --iterations flag"), "{}", err);
+    }
+
+    #[test]
+    fn values_starting_with_dashes_can_be_given() {
+        let options = parse(&["--prefix=--", "--compare", "--learn-rate 0.01"]).unwrap();
+        assert_eq!(options.prefix, "--");
+        assert_eq!(options.compare_flags.as_deref(), Some("--learn-rate 0.01"));
+    }
+
+    #[test]
+    fn compare_flags_keep_quoted_values_together() {
+        let mut options = parse(&["--compare", "--data 'my names.txt' --prefix \"a b\" --iterations 5"]).unwrap();
+        parse_compare(&mut options).unwrap();
+        let compare = options.compare.unwrap();
+        assert_eq!(compare.data, "my names.txt");
+        assert_eq!(compare.prefix, "a b");
+        assert_eq!(compare.iterations, 5);
+
+        assert!(split_flags("--data 'my names.txt").is_err());
+        assert_eq!(split_flags(" --prefix '' ").unwrap(), ["--prefix", ""]);
+    }
 }
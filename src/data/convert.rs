@@ -8,6 +8,15 @@ pub const LETTERS: &[char] = &[
     '.', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
 ];
 
+// How the optional end-of-word token is displayed, it's placed just past the letters. When it's enabled the
+// '.' character only marks the start of words.
+pub const END_CHAR: char = '$';
+
+// The number of tokens the model predicts over.
+pub fn vocab_size(end_token: bool) -> usize {
+    if end_token { LETTERS.len() + 1 } else { LETTERS.len() }
+}
+
 // Convert an normalized integer to a letter.
 pub fn itol(index: u8) -> char {
     return LETTERS.get(usize::from(index)).unwrap_or(&'z').clone();
@@ -0,0 +1,85 @@
+use crate::{data::vocab::Vocab, error::VibeError};
+
+// Power iterations spent on each eigenvector.
+const POWER_ITERATIONS: usize = 100;
+
+// Embeddings for every token from the leading eigenvectors of the token bigram co-occurrence matrix, as a row
+// major vocab x embedding_size table scaled into the range of the random initialization.
+//
+// NOTE: the counts are symmetrized and log scaled so the matrix is symmetric, then its eigenvectors are found
+// by power iteration with deflation, which is plenty for a vocabulary this small. Each eigenvector is weighted
+// by the square root of its eigenvalue like a truncated SVD, and dimensions past the vocabulary size are 0.
+pub fn embeddings(words: &[String], vocab: &Vocab, embedding_size: usize) -> Result<Vec<f32>, VibeError> {
+    let size = vocab.size();
+
+    // Words start after the '.' delimiter and finish on the end token.
+    let mut counts = vec![vec![0f64; size]; size];
+    for word in words {
+        let mut previous = 0;
+        for token in vocab.encode(word)?.into_iter().chain([vocab.end()]) {
+            counts[previous as usize][token as usize] += 1.;
+            previous = token;
+        }
+    }
+
+    let mut matrix = vec![vec![0f64; size]; size];
+    for row in 0..size {
+        for column in 0..size {
+            matrix[row][column] = (1. + counts[row][column] + counts[column][row]).ln();
+        }
+    }
+
+    let mut table = vec![0f32; size * embedding_size];
+    for dimension in 0..embedding_size.min(size) {
+        let (value, vector) = leading_eigenvector(&matrix, dimension);
+        for (row, component) in vector.iter().enumerate() {
+            table[row * embedding_size + dimension] = (component * value.abs().sqrt()) as f32;
+        }
+
+        // Remove this eigenvector so the next iteration finds the following one.
+        for row in 0..size {
+            for column in 0..size {
+                matrix[row][column] -= value * vector[row] * vector[column];
+            }
+        }
+    }
+
+    let largest = table.iter().fold(0f32, |largest, value| largest.max(value.abs()));
+    if largest > 0. {
+        table.iter_mut().for_each(|value| *value /= largest);
+    }
+
+    Ok(table)
+}
+
+// The eigenvalue and unit eigenvector with the largest magnitude, starting from a vector that varies with the
+// dimension so deflated matrices don't start orthogonal to their next eigenvector.
+fn leading_eigenvector(matrix: &[Vec<f64>], dimension: usize) -> (f64, Vec<f64>) {
+    let size = matrix.len();
+    let mut vector: Vec<f64> = (0..size).map(|index| 1. + ((index + dimension) % 7) as f64).collect();
+    normalize(&mut vector);
+
+    for _ in 0..POWER_ITERATIONS {
+        let mut next = multiply(matrix, &vector);
+        if normalize(&mut next) == 0. {
+            break;
+        }
+        vector = next;
+    }
+
+    let value = multiply(matrix, &vector).iter().zip(&vector).map(|(a, b)| a * b).sum();
+    (value, vector)
+}
+
+fn multiply(matrix: &[Vec<f64>], vector: &[f64]) -> Vec<f64> {
+    matrix.iter().map(|row| row.iter().zip(vector).map(|(a, b)| a * b).sum()).collect()
+}
+
+// Scale a vector to unit length, returning its length before.
+fn normalize(vector: &mut [f64]) -> f64 {
+    let length = vector.iter().map(|value| value * value).sum::<f64>().sqrt();
+    if length > 0. {
+        vector.iter_mut().for_each(|value| *value /= length);
+    }
+    length
+}
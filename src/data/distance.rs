@@ -0,0 +1,29 @@
+// The Levenshtein distance between two strings, the fewest single character insertions, deletions and
+// substitutions turning one into the other. Anything over the limit is None.
+//
+// NOTE: the distance only grows along a row of the table, so once a whole row is past the limit the rest of
+// the table can't bring it back and the comparison stops early.
+pub fn levenshtein(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current: Vec<usize> = vec![0; b.len() + 1];
+    for (i, &letter) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &other) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(letter != other);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        if current.iter().all(|&distance| distance > limit) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    Some(previous[b.len()]).filter(|&distance| distance <= limit)
+}
@@ -0,0 +1,26 @@
+use std::collections::HashMap;
+
+// The entropy in nats of the next token given the context window before it, estimated from how often each
+// token follows each context in the data. A model working from the same window can't do better on this data.
+//
+// NOTE: the estimate is in-sample, a context seen only once looks perfectly predictable, so it's an optimistic
+// floor that gets looser with longer blocks and smaller data sets.
+pub fn conditional_entropy(contexts: &[Vec<u32>], targets: &[u32]) -> f32 {
+    let mut counts: HashMap<&[u32], HashMap<u32, usize>> = HashMap::new();
+    for (context, target) in contexts.iter().zip(targets) {
+        *counts.entry(context.as_slice()).or_default().entry(*target).or_insert(0) += 1;
+    }
+
+    let total = targets.len().max(1) as f64;
+    let entropy: f64 = counts
+        .values()
+        .map(|next| {
+            let context_count = next.values().sum::<usize>() as f64;
+            next.values()
+                .map(|&count| -(count as f64 / total) * (count as f64 / context_count).ln())
+                .sum::<f64>()
+        })
+        .sum();
+
+    entropy as f32
+}
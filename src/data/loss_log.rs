@@ -0,0 +1,167 @@
+// Loss logs are CSV files with a `kind,iteration,loss` header and one row per recorded loss, where kind is
+// `training`, `validation` or `evaluation`.
+
+use crate::{app::message::LossType, error::VibeError};
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, LineWriter, Write},
+};
+
+pub const LOSS_LOG_HEADER: &str = "kind,iteration,loss";
+pub const KIND_TRAINING: &str = "training";
+pub const KIND_VALIDATION: &str = "validation";
+pub const KIND_EVALUATION: &str = "evaluation";
+
+// Read the training losses of a loss log as chart points, for overlaying a previous run.
+//
+// Rows with only `iteration,loss` are accepted as training losses so hand-made logs work too.
+pub fn read_baseline(path: &String) -> Result<Vec<(f64, f64)>, VibeError> {
+    let content = fs::read_to_string(path).map_err(|e| VibeError::new(format!("unable to open baseline {}: {}", path, e)))?;
+
+    let mut points: Vec<(f64, f64)> = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line == LOSS_LOG_HEADER {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+        let (iteration, loss) = match fields.as_slice() {
+            [kind, iteration, loss] => {
+                if *kind != KIND_TRAINING {
+                    continue;
+                }
+                (iteration, loss)
+            }
+            [iteration, loss] => (iteration, loss),
+            _ => {
+                return Err(VibeError::new(format!(
+                    "malformed baseline row {} in {}: {}",
+                    number + 1,
+                    path,
+                    line
+                )));
+            }
+        };
+
+        points.push((str::parse::<f64>(iteration)?, str::parse::<f64>(loss)?));
+    }
+
+    Ok(points)
+}
+
+// Writer for recording every loss of a run as a loss log.
+pub struct LossLog {
+    writer: BufWriter<File>,
+}
+
+impl LossLog {
+    // Create (or truncate) the loss log at the given path and write the header.
+    pub fn create(path: &String) -> Result<Self, VibeError> {
+        let file = File::create(path).map_err(|e| VibeError::new(format!("unable to create loss log {}: {}", path, e)))?;
+
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "{}", LOSS_LOG_HEADER)?;
+
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, loss_type: &LossType, iteration: usize, loss: f32) -> Result<(), VibeError> {
+        let kind = match loss_type {
+            LossType::Training => KIND_TRAINING,
+            LossType::Validation => KIND_VALIDATION,
+            LossType::Evaluation => KIND_EVALUATION,
+        };
+        writeln!(self.writer, "{},{},{}", kind, iteration, loss)?;
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), VibeError> {
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+// Writer streaming losses as `kind\titeration\tloss` lines as they arrive, for piping into a live plot.
+pub struct LossStream {
+    writer: LineWriter<File>,
+}
+
+impl LossStream {
+    // Open a stream to either an already open file descriptor given by number, or a file or named pipe
+    // path. Opening a named pipe blocks until something reads from it.
+    pub fn open(target: &String) -> Result<Self, VibeError> {
+        let file = match str::parse::<i32>(target) {
+            Ok(fd) => open_fd(fd)?,
+            Err(_) => OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(target)
+                .map_err(|e| VibeError::new(format!("unable to open loss stream {}: {}", target, e)))?,
+        };
+
+        Ok(Self {
+            writer: LineWriter::new(file),
+        })
+    }
+
+    pub fn write(&mut self, loss_type: &LossType, iteration: usize, loss: f32) -> Result<(), VibeError> {
+        let kind = match loss_type {
+            LossType::Training => KIND_TRAINING,
+            LossType::Validation => KIND_VALIDATION,
+            LossType::Evaluation => KIND_EVALUATION,
+        };
+        writeln!(self.writer, "{}\t{}\t{}", kind, iteration, loss)?;
+
+        Ok(())
+    }
+}
+
+// Open an inherited file descriptor through /dev/fd, which gives the stream its own descriptor so the
+// inherited one is left to its owner. The interface owns stdin and stdout so those are refused.
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<File, VibeError> {
+    if fd <= 1 {
+        return Err(VibeError::new(format!("file descriptor {} is used by the interface", fd)));
+    }
+
+    OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{}", fd))
+        .map_err(|e| VibeError::new(format!("file descriptor {} is not open for writing: {}", fd, e)))
+}
+
+#[cfg(not(unix))]
+fn open_fd(fd: i32) -> Result<File, VibeError> {
+    Err(VibeError::new(format!(
+        "file descriptor {} can't be used on this platform, pass a path instead",
+        fd
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn streams_to_an_open_descriptor_without_taking_it() {
+        use std::os::fd::AsRawFd;
+
+        let path = std::env::temp_dir().join(format!("gpturd-loss-fd-{}.tsv", std::process::id()));
+        fs::write(&path, "").unwrap();
+        let file = OpenOptions::new().append(true).open(&path).unwrap();
+        let mut stream = LossStream::open(&file.as_raw_fd().to_string()).unwrap();
+        stream.write(&LossType::Training, 3, 1.5).unwrap();
+        drop(stream);
+
+        // The descriptor is still the test's own after the stream closed its copy.
+        writeln!(&file, "done").unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written, format!("{}\t3\t1.5\ndone\n", KIND_TRAINING));
+    }
+}
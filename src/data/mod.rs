@@ -1,3 +1,9 @@
 pub mod convert;
+pub mod cooccurrence;
+pub mod distance;
+pub mod entropy;
+pub mod loss_log;
+pub mod output;
 pub mod parse;
 pub mod tokenize;
+pub mod vocab;
@@ -0,0 +1,105 @@
+// Generated words written to a file as plain text with one word per line, a CSV with a `word,likelihood,novel`
+// header, or a JSON array of objects with the same fields. Words are written in the order they were generated.
+
+use crate::error::VibeError;
+
+use std::fs;
+
+pub const OUTPUT_FORMAT_NAME_TEXT: &str = "text";
+pub const OUTPUT_FORMAT_NAME_CSV: &str = "csv";
+pub const OUTPUT_FORMAT_NAME_JSON: &str = "json";
+
+const CSV_HEADER: &str = "word,likelihood,novel";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().as_str() {
+            OUTPUT_FORMAT_NAME_TEXT => Ok(Self::Text),
+            OUTPUT_FORMAT_NAME_CSV => Ok(Self::Csv),
+            OUTPUT_FORMAT_NAME_JSON => Ok(Self::Json),
+            _ => Err(VibeError::new(format!(
+                "invalid output format {}, expected {}, {} or {}",
+                name, OUTPUT_FORMAT_NAME_TEXT, OUTPUT_FORMAT_NAME_CSV, OUTPUT_FORMAT_NAME_JSON
+            ))),
+        }
+    }
+}
+
+// A generated word with how the model rated it.
+pub struct OutputWord {
+    pub word: String,
+    // The geometric mean of the per-character probabilities.
+    pub likelihood: f32,
+    // Whether the word isn't in the training set, unknown without training data.
+    pub novel: Option<bool>,
+}
+
+// Write a batch of generated words, replacing whatever the file held before.
+pub fn write(path: &str, format: OutputFormat, words: &[OutputWord]) -> Result<(), VibeError> {
+    let content = match format {
+        OutputFormat::Text => words.iter().map(|word| format!("{}\n", word.word)).collect::<String>(),
+        OutputFormat::Csv => {
+            let mut content = format!("{}\n", CSV_HEADER);
+            for word in words {
+                content.push_str(&format!(
+                    "{},{},{}\n",
+                    csv_field(&word.word),
+                    word.likelihood,
+                    word.novel.map_or(String::new(), |novel| novel.to_string())
+                ));
+            }
+            content
+        }
+        OutputFormat::Json => {
+            let objects: Vec<String> = words
+                .iter()
+                .map(|word| {
+                    format!(
+                        "  {{\"word\": {}, \"likelihood\": {}, \"novel\": {}}}",
+                        json_string(&word.word),
+                        word.likelihood,
+                        word.novel.map_or("null".to_string(), |novel| novel.to_string())
+                    )
+                })
+                .collect();
+            format!("[\n{}\n]\n", objects.join(",\n"))
+        }
+    };
+
+    fs::write(path, content).map_err(|e| VibeError::new(format!("unable to write generated words to {}: {}", path, e)))
+}
+
+// Quote a CSV field when it holds a separator, quote or line break, doubling any quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Quote a JSON string, escaping quotes, backslashes and control characters.
+pub fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for letter in text.chars() {
+        match letter {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            letter if letter.is_control() => quoted.push_str(&format!("\\u{:04x}", letter as u32)),
+            letter => quoted.push(letter),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
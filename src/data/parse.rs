@@ -1,47 +1,195 @@
-use crate::{data::tokenize, error::VibeError};
+use crate::{
+    data::{
+        tokenize::{self, Padding},
+        vocab::Vocab,
+    },
+    error::VibeError,
+};
 
 use candle_core::{Device, Tensor};
-use rand::seq::SliceRandom;
-use std::fs;
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
 
 #[derive(Clone, Debug)]
 pub struct Data {
     pub input: Tensor,
     pub target: Tensor,
+    pub positions: Vec<usize>,
     pub validation_input: Tensor,
     pub validation_target: Tensor,
+    // Where in its word each validation target is, the end token counting as the position after the last.
+    pub validation_positions: Vec<usize>,
+    pub word_count: usize,
+    pub trimmed_count: usize,
+    pub training_words: HashSet<String>,
+}
+
+impl Data {
+    // Copy the tensors onto another device.
+    pub fn to_device(&self, device: &Device) -> Result<Self, VibeError> {
+        Ok(Self {
+            input: self.input.to_device(device)?,
+            target: self.target.to_device(device)?,
+            validation_input: self.validation_input.to_device(device)?,
+            validation_target: self.validation_target.to_device(device)?,
+            ..self.clone()
+        })
+    }
 }
 
 pub const DEFAULT_DATA_PATH: &str = "data/names_short.txt";
 
+// How the bytes of a data file are turned into text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    // Refuse files that aren't valid UTF-8.
+    Utf8,
+    // Every byte is its own character, which can't fail.
+    Latin1,
+    // Decode UTF-8, replacing invalid bytes with U+FFFD.
+    Lossy,
+}
+
+impl Encoding {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().replace('-', "").as_str() {
+            "utf8" => Ok(Self::Utf8),
+            "latin1" => Ok(Self::Latin1),
+            "lossy" => Ok(Self::Lossy),
+            _ => Err(VibeError::new(format!(
+                "invalid encoding {}, expected utf-8, latin-1 or lossy",
+                name
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Latin1 => "latin-1",
+            Self::Lossy => "lossy",
+        }
+    }
+
+    // Decode the bytes of a file.
+    fn decode(&self, path: &str, bytes: Vec<u8>) -> Result<String, VibeError> {
+        match self {
+            Self::Utf8 => String::from_utf8(bytes).map_err(|e| {
+                VibeError::new(format!(
+                    "{} isn't valid UTF-8 ({}), read it with --encoding latin-1 or --encoding lossy",
+                    path,
+                    e.utf8_error()
+                ))
+            }),
+            Self::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+            Self::Lossy => Ok(String::from_utf8_lossy(&bytes).into_owned()),
+        }
+    }
+}
+
+// Lines longer than this are almost certainly not single words, e.g. a file without any newlines.
+const MAX_WORD_LENGTH: usize = 256;
+
 // Read the data into a list of strings using newlines as a separator.
-fn parse_data(path: &String) -> Result<Vec<String>, VibeError> {
-    let content = fs::read_to_string(path).map_err(|e| VibeError::new(format!("unable to open {}: {}", path, e)))?;
+//
+// Files saved on Windows can start with a byte order mark and end lines with CRLF or a bare CR, those are
+// normalized away so they don't end up as part of a word. Words can also be split on another character for files
+// that aren't one word per line, otherwise an overly long line is refused before it gets tokenized.
+pub fn parse_data(path: &String, split_on: Option<char>, encoding: Encoding) -> Result<Vec<String>, VibeError> {
+    let bytes = fs::read(path).map_err(|e| VibeError::new(format!("unable to open {}: {}", path, e)))?;
+    let content = encoding.decode(path, bytes)?;
+    let content = content
+        .strip_prefix('\u{feff}')
+        .unwrap_or(&content)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n");
+
+    let content = match split_on {
+        Some(separator) => content.replace(separator, "\n"),
+        None => content,
+    };
 
     let items: Vec<String> = content.lines().map(|elem| String::from(elem).trim().to_lowercase()).collect();
 
+    if let Some((line, item)) = items.iter().enumerate().find(|(_, item)| item.chars().count() > MAX_WORD_LENGTH) {
+        return Err(VibeError::new(format!(
+            "line {} of {} is {} characters long but should be a single word, put one word per line or break it up with --split-on",
+            line + 1,
+            path,
+            item.chars().count()
+        )));
+    }
+
     Ok(items)
 }
 
-// Randomize the input data, break it into different data sets, then tokenize and convert to
+// Break the shuffled data into different data sets, then tokenize and convert to
 // tensors for training..
 //
 // The two different data sets will be the training set and the validation set. The training set
 // is used for model training, the validation set is a set of valid words the model hasn't been
-// trained on that we can validate against.
-pub fn training_data(path: &String, block_size: usize, device: &Device) -> Result<Data, VibeError> {
-    let mut data = parse_data(path)?;
-    data.shuffle(&mut rand::rng());
+// trained on that we can validate against. Without validation everything goes to training.
+pub fn training_data(
+    mut data: Vec<String>,
+    block_size: usize,
+    vocab: &Vocab,
+    padding: Padding,
+    frequency_cap: usize,
+    validation: bool,
+    device: &Device,
+) -> Result<Data, VibeError> {
+    // Keep any single word from dominating by dropping its occurrences past the cap.
+    let mut trimmed_count = 0;
+    if frequency_cap > 0 {
+        let mut occurrences: HashMap<String, usize> = HashMap::new();
+        data.retain(|word| {
+            let count = occurrences.entry(word.clone()).or_insert(0);
+            *count += 1;
+            if *count > frequency_cap {
+                trimmed_count += 1;
+            }
+            *count <= frequency_cap
+        });
+    }
 
-    let training_end = (data.len() as f64 * 0.9).round() as usize;
+    let training_end = if validation {
+        (data.len() as f64 * 0.9).round() as usize
+    } else {
+        data.len()
+    };
 
-    let (input, target) = tokenize::tokenize(&data[..training_end].to_vec(), block_size, device)?;
-    let (validation_input, validation_target) = tokenize::tokenize(&data[training_end..].to_vec(), block_size, device)?;
+    let (input, target, positions) = tokenize::tokenize(&data[..training_end].to_vec(), block_size, vocab, padding, device)?;
+    let (validation_input, validation_target, validation_positions) =
+        tokenize::tokenize(&data[training_end..].to_vec(), block_size, vocab, padding, device)?;
 
     Ok(Data {
         input: input,
         target: target,
+        positions,
         validation_input: validation_input,
         validation_target: validation_target,
+        validation_positions,
+        word_count: data.len(),
+        trimmed_count,
+        training_words: data[..training_end].iter().cloned().collect(),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bom_and_crlf_are_stripped() {
+        let path = std::env::temp_dir().join(format!("gpturd-bom-{}.txt", std::process::id()));
+        fs::write(&path, "\u{feff}Anna\r\nBob\r\nCarol\rdave\n").unwrap();
+
+        let path = path.to_string_lossy().to_string();
+        let words = parse_data(&path, None, Encoding::Utf8).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(words, vec!["anna", "bob", "carol", "dave"]);
+    }
+}
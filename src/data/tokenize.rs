@@ -1,35 +1,314 @@
-use crate::{data::convert, error::VibeError};
+use crate::{
+    app::options::Options,
+    data::{convert, parse, vocab::Vocab},
+    error::VibeError,
+};
 
 use candle_core::{Device, Tensor};
 
+pub const TOKENIZER_NAME_CHAR: &str = "char";
+pub const TOKENIZER_NAME_SYLLABLE: &str = "syllable";
+
+pub const PADDING_NAME_DELIMITER: &str = "delimiter";
+pub const PADDING_NAME_REPEAT_FIRST: &str = "repeat-first";
+pub const PADDING_NAME_NONE: &str = "none";
+
+// The most mismatches printed by the round trip check.
+const VERIFY_SAMPLES: usize = 10;
+
+// How words are broken into tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Tokenizer {
+    Char,
+    Syllable,
+}
+
+impl Tokenizer {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name {
+            TOKENIZER_NAME_CHAR => Ok(Self::Char),
+            TOKENIZER_NAME_SYLLABLE => Ok(Self::Syllable),
+            _ => Err(VibeError::new(format!("invalid tokenizer: {}", name))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Char => TOKENIZER_NAME_CHAR,
+            Self::Syllable => TOKENIZER_NAME_SYLLABLE,
+        }
+    }
+}
+
+// What fills the context window before a word has enough tokens of its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Padding {
+    // The '.' delimiter.
+    Delimiter,
+    // The first token of the word, once there is one. The first token itself is predicted from delimiters.
+    RepeatFirst,
+    // Nothing, positions without a full window of real tokens aren't trained on.
+    None,
+}
+
+impl Padding {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name.trim().to_lowercase().as_str() {
+            PADDING_NAME_DELIMITER => Ok(Self::Delimiter),
+            PADDING_NAME_REPEAT_FIRST => Ok(Self::RepeatFirst),
+            PADDING_NAME_NONE => Ok(Self::None),
+            _ => Err(VibeError::new(format!(
+                "invalid padding {}, expected {}, {} or {}",
+                name, PADDING_NAME_DELIMITER, PADDING_NAME_REPEAT_FIRST, PADDING_NAME_NONE
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Delimiter => PADDING_NAME_DELIMITER,
+            Self::RepeatFirst => PADDING_NAME_REPEAT_FIRST,
+            Self::None => PADDING_NAME_NONE,
+        }
+    }
+}
+
+// Slide a context window past the token at a position within its word.
+pub fn advance(context: &mut Vec<u32>, token: u32, position: usize, padding: Padding) {
+    if position == 0 && padding == Padding::RepeatFirst {
+        context.fill(token);
+    } else {
+        context.remove(0);
+        context.push(token);
+    }
+}
+
+fn is_vowel(letter: char) -> bool {
+    matches!(letter, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+// Split a word into rough syllables around its vowel clusters.
+//
+// Each syllable is a run of vowels with the consonants leading into it. Between two vowel clusters a single
+// consonant starts the next syllable (ma-ry) while longer runs give their first consonant to the previous
+// one (em-ma), and any trailing consonants close out the last syllable. Letters are normalized the same way
+// as characters so every syllable is made of a-z.
+pub fn syllables(word: &str) -> Vec<String> {
+    let letters: Vec<char> = word.chars().map(|letter| convert::itol(convert::ltoi(letter))).collect();
+
+    let mut syllables: Vec<String> = Vec::new();
+    let mut current = String::new();
+    let mut index = 0;
+    while index < letters.len() {
+        // Leading consonants and the vowel cluster.
+        while index < letters.len() && !is_vowel(letters[index]) {
+            current.push(letters[index]);
+            index += 1;
+        }
+        while index < letters.len() && is_vowel(letters[index]) {
+            current.push(letters[index]);
+            index += 1;
+        }
+
+        // Consonants up to the next vowel, or the end of the word.
+        let next_vowel = letters[index..].iter().position(|&letter| is_vowel(letter));
+        match next_vowel {
+            Some(consonants) if consonants > 1 => {
+                current.push(letters[index]);
+                index += 1;
+            }
+            Some(_) => {}
+            None => {
+                current.extend(&letters[index..]);
+                index = letters.len();
+            }
+        }
+
+        syllables.push(std::mem::take(&mut current));
+    }
+
+    syllables
+}
+
 // Tokenize a list of strings for neural network training.
 //
-// Strings are tokenized characterwise in blocks specified by options.block_size.
-pub fn tokenize(words: &Vec<String>, block_size: usize, device: &Device) -> Result<(Tensor, Tensor), VibeError> {
-    let delimiter: char = convert::LETTERS[0];
-    let mut input: Vec<Vec<u8>> = vec![];
-    let mut target: Vec<u8> = vec![];
+// Strings are tokenized by the vocabulary's tokenizer in blocks specified by options.block_size. Contexts
+// start padded by the padding strategy and every word is terminated by the vocabulary's end token. Along with
+// the input and target tensors comes each target's position within its word.
+pub fn tokenize(
+    words: &Vec<String>,
+    block_size: usize,
+    vocab: &Vocab,
+    padding: Padding,
+    device: &Device,
+) -> Result<(Tensor, Tensor, Vec<usize>), VibeError> {
+    let mut input: Vec<Vec<u32>> = vec![];
+    let mut target: Vec<u32> = vec![];
+    let mut positions: Vec<usize> = vec![];
 
     for word in words {
-        let mut context: Vec<u8> = vec![0; block_size];
+        let mut context: Vec<u32> = vec![0; block_size];
 
-        let mut chars: Vec<char> = word.chars().collect();
-        chars.push(delimiter);
+        let mut tokens: Vec<u32> = vocab.encode(word)?;
+        tokens.push(vocab.end());
 
-        for letter in chars {
-            let letter_value = convert::ltoi(letter);
-            input.push(context.clone());
-            target.push(letter_value);
+        for (position, token) in tokens.into_iter().enumerate() {
+            if padding != Padding::None || position >= block_size {
+                input.push(context.clone());
+                target.push(token);
+                positions.push(position);
+            }
 
-            context.remove(0);
-            context.push(letter_value);
+            advance(&mut context, token, position, padding);
         }
     }
 
-    let input_tensor = Tensor::from_vec(input.iter().flatten().copied().collect(), (input.len(), input[0].len()), device)?;
+    let input_tensor = Tensor::from_vec(input.iter().flatten().copied().collect(), (input.len(), block_size), device)?;
 
     let target_len = target.len();
     let target_tensor = Tensor::from_vec(target, target_len, device)?;
 
-    Ok((input_tensor, target_tensor))
+    Ok((input_tensor, target_tensor, positions))
+}
+
+// Tokenize the training data, rebuild the words from the tensors and check they match the normalized words,
+// then print a summary with a few of the mismatches. Every context is also checked against the tokens that
+// came before it in the word.
+//
+// NOTE: anything in the data that decodes to the delimiter or the end token splits the word it's in, so it
+// shows up here as a mismatch. Without padding, a word's opening tokens are only in its first context and
+// words too short to fill one aren't there at all.
+pub fn verify(options: &Options) -> Result<(), VibeError> {
+    let mut words = parse::parse_data(&options.data, options.split_on, options.encoding)?;
+    if !options.suffix.is_empty() {
+        words = words.iter().map(|word| word.chars().rev().collect()).collect();
+    }
+    let mut vocab = Vocab::new(options.tokenizer, options.end_token, &words);
+    if options.prune_vocab {
+        vocab = vocab.pruned(&words);
+    }
+    let block_size = options.block_size;
+    let padding = options.padding;
+
+    let (input, target, _) = tokenize(&words, block_size, &vocab, padding, &Device::Cpu)?;
+    let input = input.to_vec2::<u32>()?;
+    let target = target.to_vec1::<u32>()?;
+
+    // Rebuild words token by token, starting a new one after each end token.
+    let mut rebuilt: Vec<String> = Vec::new();
+    let mut tokens: Vec<u32> = Vec::new();
+    let mut expected_context: Vec<u32> = vec![0; block_size];
+    let mut context_mismatches: Vec<String> = Vec::new();
+    for (context, &token) in input.iter().zip(&target) {
+        if tokens.is_empty() && padding == Padding::None {
+            tokens = context.clone();
+            expected_context = context.clone();
+        }
+
+        if *context != expected_context {
+            context_mismatches.push(format!(
+                "context {} where {} was expected",
+                vocab.display(context),
+                vocab.display(&expected_context)
+            ));
+        }
+
+        if token == vocab.end() {
+            rebuilt.push(tokens.iter().map(|&index| vocab.token(index)).collect());
+            tokens.clear();
+            expected_context = vec![0; block_size];
+        } else {
+            advance(&mut expected_context, token, tokens.len(), padding);
+            tokens.push(token);
+        }
+    }
+
+    let expected: Vec<String> = words
+        .iter()
+        .filter(|word| padding != Padding::None || vocab.encode(word).is_ok_and(|tokens| tokens.len() >= block_size))
+        .map(|word| word.chars().map(|letter| convert::itol(convert::ltoi(letter))).collect())
+        .collect();
+    let word_mismatches: Vec<String> = expected
+        .iter()
+        .zip(&rebuilt)
+        .filter(|(expected, rebuilt)| expected != rebuilt)
+        .map(|(expected, rebuilt)| format!("{} rebuilt as {}", expected, rebuilt))
+        .collect();
+
+    println!(
+        "{} tokenization of {} ({} words, {} examples, block size {}, {} padding)",
+        vocab.tokenizer().name(),
+        options.data,
+        words.len(),
+        target.len(),
+        block_size,
+        padding.name()
+    );
+    println!("\trebuilt words      {:>8}", rebuilt.len());
+    println!("\tword mismatches    {:>8}", word_mismatches.len());
+    println!("\tcontext mismatches {:>8}", context_mismatches.len());
+    for mismatch in word_mismatches.iter().chain(&context_mismatches).take(VERIFY_SAMPLES) {
+        println!("\t\t{}", mismatch);
+    }
+
+    if rebuilt.len() == expected.len() && word_mismatches.is_empty() && context_mismatches.is_empty() {
+        println!("PASS");
+        Ok(())
+    } else {
+        println!("FAIL");
+        Err(VibeError::new(format!(
+            "tokenization round trip failed: {} of {} words rebuilt, {} word and {} context mismatches",
+            rebuilt.len(),
+            expected.len(),
+            word_mismatches.len(),
+            context_mismatches.len()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // "ab" and "abcd" have 3 and 5 targets counting the delimiter that ends them.
+    fn examples(padding: Padding) -> (Vec<Vec<u32>>, Vec<usize>) {
+        let vocab = Vocab::new(Tokenizer::Char, false, &[]);
+        let words = vec!["ab".to_string(), "abcd".to_string()];
+        let (input, _, positions) = tokenize(&words, 3, &vocab, padding, &Device::Cpu).unwrap();
+
+        (input.to_vec2::<u32>().unwrap(), positions)
+    }
+
+    #[test]
+    fn delimiter_padding_keeps_every_position() {
+        let (input, positions) = examples(Padding::Delimiter);
+        assert_eq!(input.len(), 8);
+        assert_eq!(positions, vec![0, 1, 2, 0, 1, 2, 3, 4]);
+        assert_eq!(input[0], vec![0, 0, 0]);
+        assert_eq!(input[1], vec![0, 0, 1]);
+    }
+
+    #[test]
+    fn repeat_first_padding_keeps_every_position() {
+        let (input, positions) = examples(Padding::RepeatFirst);
+        assert_eq!(input.len(), 8);
+        assert_eq!(positions, vec![0, 1, 2, 0, 1, 2, 3, 4]);
+        assert_eq!(input[0], vec![0, 0, 0]);
+        assert_eq!(input[1], vec![1, 1, 1]);
+        assert_eq!(input[2], vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn no_padding_skips_positions_without_a_full_context() {
+        let (input, positions) = examples(Padding::None);
+        assert_eq!(input.len(), 2);
+        assert_eq!(positions, vec![3, 4]);
+        assert_eq!(input[0], vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn padding_names_ignore_case_and_whitespace() {
+        assert_eq!(Padding::from_name(" Repeat-First ").unwrap(), Padding::RepeatFirst);
+        assert!(Padding::from_name("left").is_err());
+    }
 }
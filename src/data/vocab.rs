@@ -0,0 +1,320 @@
+use crate::{
+    app::options::Options,
+    data::{
+        convert, parse,
+        tokenize::{self, Tokenizer},
+    },
+    error::VibeError,
+};
+
+use std::collections::{BTreeSet, HashMap};
+
+// The tokens the model predicts over. Index 0 is always the '.' delimiter, followed by either the letters or
+// the syllables seen in the data, then the optional end token.
+#[derive(Debug, Clone)]
+pub struct Vocab {
+    tokenizer: Tokenizer,
+    tokens: Vec<String>,
+    end_token: bool,
+    // The index of each token, and of each normalized letter by its ltoi number so encoding characters
+    // doesn't have to build a string for every one.
+    indices: HashMap<String, u32>,
+    letters: Vec<Option<u32>>,
+    // The most characters in any one token, no longer match has to be looked for.
+    longest: usize,
+}
+
+impl Vocab {
+    // Build the vocabulary for a tokenizer, syllables are collected from the given words.
+    pub fn new(tokenizer: Tokenizer, end_token: bool, words: &[String]) -> Self {
+        let tokens = match tokenizer {
+            Tokenizer::Char => convert::LETTERS.iter().map(|letter| letter.to_string()).collect(),
+            Tokenizer::Syllable => {
+                let syllables: BTreeSet<String> = words.iter().flat_map(|word| tokenize::syllables(word)).collect();
+                std::iter::once(".".to_string()).chain(syllables).collect()
+            }
+        };
+
+        Self::from_tokens(tokenizer, tokens, end_token)
+    }
+
+    fn from_tokens(tokenizer: Tokenizer, tokens: Vec<String>, end_token: bool) -> Self {
+        let indices: HashMap<String, u32> = tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| (token.clone(), index as u32))
+            .collect();
+        let letters = convert::LETTERS
+            .iter()
+            .map(|letter| indices.get(&letter.to_string()).copied())
+            .collect();
+        let longest = tokens.iter().map(|token| token.chars().count()).max().unwrap_or(1);
+
+        Self {
+            tokenizer,
+            tokens,
+            end_token,
+            indices,
+            letters,
+            longest,
+        }
+    }
+
+    // The same vocabulary with the tokens a model was saved with, which have to be as many as it has now so
+    // the model's shape still fits.
+    pub fn with_tokens(&self, tokens: Vec<String>) -> Result<Self, VibeError> {
+        if tokens.len() != self.tokens.len() {
+            return Err(VibeError::new(format!(
+                "it was saved with {} tokens in its vocabulary but the data gives {}",
+                tokens.len(),
+                self.tokens.len()
+            )));
+        }
+
+        Ok(Self::from_tokens(self.tokenizer, tokens, self.end_token))
+    }
+
+    // Drop the tokens none of the words use, keeping the '.' delimiter. Characters outside of a-z still collapse
+    // onto 'z' first, so it stays when they're in the words.
+    pub fn pruned(&self, words: &[String]) -> Self {
+        let used: BTreeSet<u32> = words.iter().filter_map(|word| self.encode(word).ok()).flatten().collect();
+        let tokens = self
+            .tokens
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index == 0 || used.contains(&(*index as u32)))
+            .map(|(_, token)| token.clone())
+            .collect();
+
+        Self::from_tokens(self.tokenizer, tokens, self.end_token)
+    }
+
+    pub fn tokenizer(&self) -> Tokenizer {
+        self.tokenizer
+    }
+
+    // The tokens in index order, without the end token.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    // The number of tokens, including the end token.
+    pub fn size(&self) -> usize {
+        self.tokens.len() + if self.end_token { 1 } else { 0 }
+    }
+
+    // The token that ends words, either the separate end token or the shared '.' delimiter.
+    pub fn end(&self) -> u32 {
+        if self.end_token { self.tokens.len() as u32 } else { 0 }
+    }
+
+    // The text of a token, with the end token shown as its display character.
+    pub fn token(&self, index: u32) -> String {
+        if self.end_token && index == self.end() {
+            return convert::END_CHAR.to_string();
+        }
+
+        self.tokens.get(index as usize).cloned().unwrap_or_default()
+    }
+
+    // Show a context window, syllables are separated so their boundaries are visible.
+    pub fn display(&self, context: &[u32]) -> String {
+        let separator = match self.tokenizer {
+            Tokenizer::Char => "",
+            Tokenizer::Syllable => "-",
+        };
+
+        context
+            .iter()
+            .map(|&index| self.token(index))
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+
+    // The tokens in the words the vocabulary can't represent exactly: characters that collapse onto 'z' or were
+    // pruned, or syllables it has never seen.
+    pub fn unknown(&self, words: &[String]) -> BTreeSet<String> {
+        match self.tokenizer {
+            Tokenizer::Char => words
+                .iter()
+                .flat_map(|word| word.chars())
+                .filter(|&letter| convert::itol(convert::ltoi(letter)) != letter || self.letter_index(letter).is_none())
+                .map(String::from)
+                .collect(),
+            Tokenizer::Syllable => words
+                .iter()
+                .flat_map(|word| tokenize::syllables(word))
+                .filter(|syllable| self.index(syllable).is_none())
+                .collect(),
+        }
+    }
+
+    fn index(&self, token: &str) -> Option<u32> {
+        self.indices.get(token).copied()
+    }
+
+    // The index of a character once it's normalized, collapsing onto 'z' like everywhere else.
+    fn letter_index(&self, letter: char) -> Option<u32> {
+        self.letters[usize::from(convert::ltoi(letter))]
+    }
+
+    // Convert a word into token indices.
+    //
+    // Words are split into syllables the same way the vocabulary was built, but generated words join
+    // syllables in ways the splitter wouldn't produce so those fall back to the longest known syllables.
+    //
+    // NOTE: characters outside of a-z collapse onto 'z' like they always have, but a word with a pruned letter
+    // or that can't be made from known syllables can't be represented at all.
+    pub fn encode(&self, word: &str) -> Result<Vec<u32>, VibeError> {
+        match self.tokenizer {
+            Tokenizer::Char => word
+                .chars()
+                .map(|letter| {
+                    self.letter_index(letter).ok_or_else(|| {
+                        VibeError::new(format!(
+                            "'{}' in {} was pruned from the vocabulary",
+                            convert::itol(convert::ltoi(letter)),
+                            word
+                        ))
+                    })
+                })
+                .collect(),
+            Tokenizer::Syllable => {
+                let split: Option<Vec<u32>> = tokenize::syllables(word).iter().map(|syllable| self.index(syllable)).collect();
+                match split {
+                    Some(indices) => Ok(indices),
+                    None => self.encode_longest(word),
+                }
+            }
+        }
+    }
+
+    // Greedily take the longest known syllable from the front of the word, no longer than the longest token.
+    fn encode_longest(&self, word: &str) -> Result<Vec<u32>, VibeError> {
+        let letters: Vec<char> = word.chars().map(|letter| convert::itol(convert::ltoi(letter))).collect();
+
+        let mut indices: Vec<u32> = Vec::new();
+        let mut start = 0;
+        while start < letters.len() {
+            let (end, index) = (start + 1..=letters.len().min(start + self.longest))
+                .rev()
+                .find_map(|end| {
+                    self.index(&letters[start..end].iter().collect::<String>())
+                        .map(|index| (end, index))
+                })
+                .ok_or_else(|| VibeError::new(format!("{} can't be made from the syllables in the vocabulary", word)))?;
+            indices.push(index);
+            start = end;
+        }
+
+        Ok(indices)
+    }
+}
+
+// Print each token with its index and how often it appears in the training data.
+//
+// The '.' delimiter is counted once per word for the start, and again for the end unless the separate end
+// token is enabled. Characters outside of a-z are counted under 'z' as the model sees them, and also broken
+// out separately so the collapse is easy to spot.
+pub fn print(options: &Options) -> Result<(), VibeError> {
+    let words = parse::parse_data(&options.data, options.split_on, options.encoding)?;
+    let vocab = Vocab::new(options.tokenizer, options.end_token, &words);
+
+    let mut counts = vec![0usize; vocab.size()];
+    let mut collapsed = 0;
+    for word in &words {
+        counts[0] += 1;
+        for index in vocab.encode(word)? {
+            counts[index as usize] += 1;
+        }
+        collapsed += word
+            .chars()
+            .filter(|&letter| convert::itol(convert::ltoi(letter)) != letter)
+            .count();
+        counts[vocab.end() as usize] += 1;
+    }
+
+    let total: usize = counts.iter().sum();
+    println!(
+        "{} vocabulary for {} ({} tokens, {} words)",
+        vocab.tokenizer().name(),
+        options.data,
+        vocab.size(),
+        words.len()
+    );
+    println!("{:>5} {:>8} {:>10} {:>8}", "index", "token", "count", "share");
+    for (index, count) in counts.iter().enumerate() {
+        println!(
+            "{:>5} {:>8} {:>10} {:>7.3}%",
+            index,
+            vocab.token(index as u32),
+            count,
+            *count as f64 / total.max(1) as f64 * 100.
+        );
+    }
+
+    if collapsed > 0 {
+        println!("{} characters outside a-z were collapsed onto 'z'", collapsed);
+    }
+
+    // The delimiter and end token are always used, by every word.
+    let unused: Vec<String> = counts
+        .iter()
+        .enumerate()
+        .filter(|(_, count)| **count == 0)
+        .map(|(index, _)| vocab.token(index as u32))
+        .collect();
+    if !unused.is_empty() {
+        println!("{} unused tokens, dropped by --prune-vocab: {}", unused.len(), unused.join(" "));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(list: &[&str]) -> Vec<String> {
+        list.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn char_encoding_collapses_onto_z() {
+        let vocab = Vocab::new(Tokenizer::Char, false, &[]);
+        assert_eq!(vocab.encode("abz").unwrap(), vec![1, 2, 26]);
+        assert_eq!(vocab.encode("a-é").unwrap(), vec![1, 26, 26]);
+    }
+
+    #[test]
+    fn pruned_letters_can_not_be_encoded() {
+        let vocab = Vocab::new(Tokenizer::Char, false, &[]).pruned(&words(&["abba", "cab"]));
+        assert_eq!(vocab.tokens(), &[".", "a", "b", "c"]);
+        assert_eq!(vocab.encode("cab").unwrap(), vec![3, 1, 2]);
+        assert!(vocab.encode("dab").unwrap_err().to_string().contains("'d'"));
+        assert!(vocab.unknown(&words(&["bad"])).contains("d"));
+    }
+
+    #[test]
+    fn syllables_fall_back_to_the_longest_known() {
+        let vocab = Vocab::new(Tokenizer::Syllable, false, &words(&["mary", "emma"]));
+        assert_eq!(vocab.tokens(), &[".", "em", "ma", "ry"]);
+        assert_eq!(vocab.encode("mary").unwrap(), vec![2, 3]);
+        // "emry" splits as "em-ry" while "maem" has no split of its own and is taken greedily.
+        assert_eq!(vocab.encode("emry").unwrap(), vec![1, 3]);
+        assert_eq!(vocab.encode("maem").unwrap(), vec![2, 1]);
+        assert!(vocab.encode("mox").is_err());
+    }
+
+    #[test]
+    fn saved_tokens_replace_the_built_ones() {
+        let vocab = Vocab::new(Tokenizer::Syllable, true, &words(&["mary", "emma"]));
+        let saved = vocab.with_tokens(words(&[".", "ry", "ma", "em"])).unwrap();
+        assert_eq!(saved.encode("mary").unwrap(), vec![2, 1]);
+        assert_eq!(saved.size(), vocab.size());
+        assert_eq!(saved.end(), vocab.end());
+
+        let err = vocab.with_tokens(words(&[".", "ma"])).unwrap_err();
+        assert!(err.to_string().contains("2 tokens"));
+    }
+}
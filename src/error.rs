@@ -12,6 +12,11 @@ impl VibeError {
     pub fn new(message: impl Into<String>) -> Self {
         Self { message: message.into() }
     }
+
+    /// The message without the prefix it's displayed with, for wrapping it in another error.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 }
 
 impl fmt::Display for VibeError {
@@ -0,0 +1,86 @@
+use crate::error::VibeError;
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{LineWriter, Write},
+    time::Instant,
+};
+
+pub const LOG_LEVEL_NAME_ERROR: &str = "error";
+pub const LOG_LEVEL_NAME_WARN: &str = "warn";
+pub const LOG_LEVEL_NAME_INFO: &str = "info";
+pub const LOG_LEVEL_NAME_DEBUG: &str = "debug";
+
+// How much detail gets logged, each level includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name {
+            LOG_LEVEL_NAME_ERROR => Ok(Self::Error),
+            LOG_LEVEL_NAME_WARN => Ok(Self::Warn),
+            LOG_LEVEL_NAME_INFO => Ok(Self::Info),
+            LOG_LEVEL_NAME_DEBUG => Ok(Self::Debug),
+            _ => Err(VibeError::new(format!("invalid log level: {}", name))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Error => LOG_LEVEL_NAME_ERROR,
+            Self::Warn => LOG_LEVEL_NAME_WARN,
+            Self::Info => LOG_LEVEL_NAME_INFO,
+            Self::Debug => LOG_LEVEL_NAME_DEBUG,
+        }
+    }
+}
+
+// Appends one line per event to a log file as `seconds level event key=value ...`, never touching the
+// terminal so the interface isn't disturbed.
+pub struct Logger {
+    writer: LineWriter<File>,
+    level: Level,
+    started: Instant,
+}
+
+impl Logger {
+    pub fn open(path: &str, level: Level) -> Result<Self, VibeError> {
+        let file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|e| VibeError::new(format!("unable to open log file {}: {}", path, e)))?;
+
+        Ok(Self {
+            writer: LineWriter::new(file),
+            level,
+            started: Instant::now(),
+        })
+    }
+
+    // Log an event with its fields. Values with spaces are quoted so lines stay easy to split.
+    //
+    // NOTE: a failing log write is dropped, the log shouldn't be able to take the app down.
+    pub fn log(&mut self, level: Level, event: &str, fields: &[(&str, String)]) {
+        if level > self.level {
+            return;
+        }
+
+        let mut line = format!("{:.3} {} {}", self.started.elapsed().as_secs_f64(), level.name(), event);
+        for (key, value) in fields {
+            if value.contains(char::is_whitespace) || value.is_empty() {
+                line.push_str(&format!(" {}={:?}", key, value));
+            } else {
+                line.push_str(&format!(" {}={}", key, value));
+            }
+        }
+
+        let _ = writeln!(self.writer, "{}", line);
+    }
+}
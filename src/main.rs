@@ -1,14 +1,56 @@
 mod app;
 mod data;
 mod error;
+mod logger;
+mod metrics;
 mod model;
+mod restarts;
+mod sampler;
+mod self_test;
 mod ui;
 
-use app::app::App;
+use app::{
+    app::App,
+    manifest,
+    options::{self, Options},
+};
+use data::{tokenize, vocab};
 use error::VibeError;
 
 fn main() -> Result<(), VibeError> {
-    App::new()?.run()?;
+    let mut options = Options::new();
+    options::parse_args(&mut options)?;
+
+    // The manifest records the options as resolved, before anything runs with them.
+    if let Some(path) = &options.manifest {
+        manifest::write(&options, path)?;
+    }
+
+    // Diagnostics print straight to stdout and exit without starting the interface.
+    if options.help {
+        options::print_help();
+        return Ok(());
+    }
+    if options.print_vocab {
+        return vocab::print(&options);
+    }
+    if options.verify_tokenization {
+        return tokenize::verify(&options);
+    }
+    if let Some(path) = &options.export_json {
+        return model::export_json(&options, path);
+    }
+    if options.enumerate_top > 0 {
+        return model::enumerate_top(&options);
+    }
+    if options.self_test {
+        return self_test::run(&options);
+    }
+    if options.restarts > 0 {
+        return restarts::run(&options);
+    }
+
+    App::new(options)?.run()?;
 
     Ok(())
 }
@@ -0,0 +1,63 @@
+use crate::error::VibeError;
+
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+// Minimum time between metrics file writes.
+const WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Training metrics periodically written as a small JSON file for external monitoring.
+pub struct Metrics {
+    path: String,
+    started: Instant,
+    last_written: Option<Instant>,
+    validation_loss: Option<f32>,
+}
+
+impl Metrics {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            started: Instant::now(),
+            last_written: None,
+            validation_loss: None,
+        }
+    }
+
+    // Remember the latest validation loss for the next write.
+    pub fn record_validation(&mut self, loss: f32) {
+        self.validation_loss = Some(loss);
+    }
+
+    // Write the latest training metrics, skipping writes that come too soon after the previous one unless forced.
+    pub fn record_training(&mut self, iteration: usize, loss: f32, learn_rate: f32, force: bool) -> Result<(), VibeError> {
+        if !force && self.last_written.is_some_and(|last| last.elapsed() < WRITE_INTERVAL) {
+            return Ok(());
+        }
+
+        let validation_loss = match self.validation_loss {
+            Some(loss) => loss.to_string(),
+            None => "null".to_string(),
+        };
+
+        let content = format!(
+            "{{\"iteration\": {}, \"training_loss\": {}, \"validation_loss\": {}, \"learn_rate\": {}, \"elapsed_seconds\": {:.3}}}\n",
+            iteration,
+            loss,
+            validation_loss,
+            learn_rate,
+            self.started.elapsed().as_secs_f64(),
+        );
+
+        // Write to a temporary file and rename it over the old one so readers never see a partial write.
+        let temp_path = format!("{}.tmp", self.path);
+        fs::write(&temp_path, content).map_err(|e| VibeError::new(format!("unable to write metrics {}: {}", temp_path, e)))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| VibeError::new(format!("unable to write metrics {}: {}", self.path, e)))?;
+
+        self.last_written = Some(Instant::now());
+
+        Ok(())
+    }
+}
@@ -1,306 +1,2963 @@
 use crate::{
     app::{
         device,
-        message::{AppMessage, LossType, ModelCommandMessage, ModelResultMessage},
-        options::Options,
+        message::{
+            AppMessage, GenerationStep, GenerationSummary, Histogram, Layer, LossType, ModelCommandMessage, ModelResultMessage,
+            PositionLoss, ScoreStep,
+        },
+        options::{self, EmbeddingInit, FrozenLayers, InitDistribution, Options, PositionWeights},
     },
     data::{
-        convert,
-        parse::{self, Data},
+        convert, cooccurrence, distance, entropy,
+        loss_log::LossLog,
+        output::{self, OutputFormat, OutputWord},
+        parse::{self, Data, Encoding},
+        tokenize::{self, Padding, Tokenizer},
+        vocab::Vocab,
     },
     error::VibeError,
+    metrics::Metrics,
+    sampler::{self, Sampler},
 };
 
-use candle_core::{Device, Tensor, Var, safetensors};
+use candle_core::{Device, Shape, Tensor, Var, safetensors};
 use candle_nn::{loss, ops};
-use rand::Rng;
+use rand::{
+    Rng, RngCore, SeedableRng,
+    distr::{Distribution, weighted::WeightedIndex},
+    rngs::StdRng,
+    seq::{IndexedRandom, SliceRandom},
+};
+use regex::Regex;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+    fs,
+    ops::Range,
     path::Path,
-    sync::mpsc::{Receiver, Sender},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender},
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 pub const DEFAULT_MODEL_PATH: &str = "model.safetensors";
 
-// The vocabulary is hardcoded to the 26 letters plus the special delimiter character.
-const VOCAB_SIZE: usize = 27;
+// A word sampled ahead of time and how much of the prefix it kept, with the generator it was drawn with.
+type Draft = (Box<dyn RngCore>, (String, usize));
+
+// The number of autosave files rotated through, so the last few autosaves are kept.
+const AUTOSAVE_SLOTS: usize = 3;
+
+// How many times a word is redrawn when the filter or the ban list rejects it.
+const FILTER_ATTEMPTS: usize = 100;
+
+// The most tokens shown for each generation step, enough for every character.
+const STEP_CANDIDATES: usize = 28;
+
+// Words sampled after training to check the model hasn't collapsed onto one character, and the share of all
+// generated characters that one character needs to count as collapsed.
+const COLLAPSE_SAMPLES: usize = 20;
+const COLLAPSE_SHARE: f32 = 0.5;
+
+// The most edits a generated word can be from a training word and still have it shown as the nearest.
+const NEAREST_DISTANCE: usize = 3;
+
+// How close to ±1 a hidden activation has to be to count as saturated, where tanh is flat and passes back next
+// to no gradient.
+const SATURATION_THRESHOLD: f64 = 0.99;
+
+// The largest block size words are enumerated exactly for, past it there are too many contexts and the search
+// falls back to a beam search keeping this many partial words for each word asked for.
+const ENUMERATE_BLOCK_SIZE: usize = 2;
+const ENUMERATE_BEAM: usize = 4;
+
+// The most partial words the exact enumeration expands, and the most tokens an enumerated word can have.
+const ENUMERATE_EXPANSIONS: usize = 20_000;
+const ENUMERATE_MAX_LENGTH: usize = 24;
+
+// The number of buckets each parameter histogram is split into.
+const HISTOGRAM_BUCKETS: usize = 24;
+
+// Estimate the bytes needed to hold the model parameters and their gradients over a vocabulary of the given
+// size, or None if the sizes are so large the count overflows.
+pub fn estimate_memory(options: &Options, vocab_size: usize) -> Option<usize> {
+    let input_size = options
+        .embedding_size
+        .checked_mul(options.block_size)?
+        .checked_add(if options.prev_onehot { vocab_size } else { 0 })?;
+
+    let parameters = [
+        vocab_size.checked_mul(options.embedding_size)?,
+        input_size.checked_mul(options.hidden_size)?,
+        options.hidden_size,
+        options.hidden_size.checked_mul(vocab_size)?,
+        vocab_size,
+    ]
+    .iter()
+    .try_fold(0usize, |total, &count| total.checked_add(count))?;
+
+    // Every f32 parameter is paired with an f32 gradient during backpropagation.
+    parameters.checked_mul(2 * std::mem::size_of::<f32>())
+}
 
-#[derive(Clone)]
 pub struct Model {
     pub device: Device,
     model_file: String,
+    data_file: String,
+    frequency_cap: usize,
     c: Var,
     weights_1: Var,
     biases_1: Var,
     weights_2: Var,
     biases_2: Var,
     hyperparameters: Hyperparameters,
-    training_data: Data,
+    vocab: Vocab,
+    // Tokens of the first loaded data outside the vocabulary, later reloads can't add to them.
+    unknown_tokens: Option<BTreeSet<String>>,
+    evaluation_file: Option<String>,
+    // Generic data trained on before the training data.
+    pretrain_file: Option<String>,
+    // Input and target of the external evaluation file.
+    evaluation_data: Option<(Tensor, Tensor)>,
+    training_data: Option<Data>,
+    progress_interval: usize,
+    loss_scale: f32,
+    patience: usize,
+    // The iteration validation starts at, the losses before it mostly reflect the random init.
+    val_warmup: usize,
+    position_weights: PositionWeights,
+    autosave_every: usize,
+    autosave_count: usize,
+    sample_checkpoints: bool,
+    loss_log: Option<LossLog>,
+    metrics: Option<Metrics>,
+    sampler: Box<dyn Sampler>,
+    target_length: usize,
+    length_strength: f32,
+    // What fills the context window before a word has enough tokens of its own.
+    padding: Padding,
+    // The longest a training run can take before it stops short of its iterations.
+    max_time: Option<Duration>,
+    // Added to the logits of the first generated token when there's no prefix, built from the start weights.
+    start_bias: Option<Tensor>,
+    start_weights: Vec<(String, f32)>,
+    visualize_delay: Option<Duration>,
+    // The start of generated words in model order, the reversed suffix when generating in reverse.
+    prefix: String,
+    reverse: bool,
+    filter: Option<Regex>,
+    // Substrings generated words can't contain, normalized like the training words.
+    ban_list: Vec<String>,
+    // Exponential moving average of the parameters over training steps, off with a decay of 0.
+    ema_decay: f32,
+    ema: Option<Parameters>,
+    generate_ema: bool,
+    hard_mining: bool,
+    balance_starts: bool,
+    // Whether part of the data is held out for validation.
+    validation: bool,
+    generate_seed: Option<u64>,
+    // Seeds the data shuffle and every training batch. Autosaves record it so a resumed run shuffles the same
+    // split and draws the same batches as one that was never interrupted.
+    train_seed: u64,
+    // The batch size asked for, which may be clamped down to the size of the training set.
+    requested_batch_size: usize,
+    clamp_batch: bool,
+    // Weight of the uniform distribution mixed into the training targets, off with a weight of 0.
+    label_smoothing: f64,
+    // Set from the app thread to stop a generation early.
+    cancel: Arc<AtomicBool>,
+    // Set from the app thread when it quits, stopping training between iterations.
+    stop_training: Arc<AtomicBool>,
+    // How many words are sampled together, one at a time when 1.
+    generate_parallel: usize,
+    // How the embeddings start, and whether the parameters are still the fresh ones from `init`.
+    embedding_init: EmbeddingInit,
+    fresh: bool,
+    init_distribution: InitDistribution,
+    split_on: Option<char>,
+    encoding: Encoding,
+    // Characters of the loaded data that couldn't be decoded and were replaced.
+    replaced_count: usize,
+    // The likelihood generated words need to be added back to the training data, off without one, and how many
+    // of them can be added as a fraction of the real words.
+    distill: Option<f32>,
+    distill_cap: f32,
+    synthetic_count: usize,
+    // How often the weights and gradients are histogrammed, off with an interval of 0.
+    debug_histograms: usize,
+    // How often a word is sampled to show while training, off with an interval of 0.
+    sample_every: usize,
+    // How often the saturation of the hidden layer is measured, off with an interval of 0.
+    debug_saturation: usize,
+    capitalize: bool,
+    suffix_append: String,
+    // Where each batch of generated words is written and in what format.
+    output: Option<(String, OutputFormat)>,
+    // Which run this model is when comparing, the main one is 0.
+    run: usize,
+}
+
+// A detached copy of the model parameters.
+struct Parameters {
+    c: Tensor,
+    weights_1: Tensor,
+    biases_1: Tensor,
+    weights_2: Tensor,
+    biases_2: Tensor,
+}
+
+impl Parameters {
+    // Move these parameters toward another set, keeping `decay` of the current values.
+    fn blend(&self, other: &Parameters, decay: f64) -> Result<Parameters, VibeError> {
+        let blend = |own: &Tensor, other: &Tensor| -> Result<Tensor, VibeError> {
+            Ok((own.affine(decay, 0.)? + other.affine(1. - decay, 0.)?)?.detach())
+        };
+
+        Ok(Parameters {
+            c: blend(&self.c, &other.c)?,
+            weights_1: blend(&self.weights_1, &other.weights_1)?,
+            biases_1: blend(&self.biases_1, &other.biases_1)?,
+            weights_2: blend(&self.weights_2, &other.weights_2)?,
+            biases_2: blend(&self.biases_2, &other.biases_2)?,
+        })
+    }
+
+    // Copy these parameters onto another device.
+    fn to_device(&self, device: &Device) -> Result<Parameters, VibeError> {
+        Ok(Parameters {
+            c: self.c.to_device(device)?,
+            weights_1: self.weights_1.to_device(device)?,
+            biases_1: self.biases_1.to_device(device)?,
+            weights_2: self.weights_2.to_device(device)?,
+            biases_2: self.biases_2.to_device(device)?,
+        })
+    }
+}
+
+// How words were enumerated. A best first search is exact unless it hit the expansion or length limit before
+// finding every word asked for, a beam search never is.
+#[derive(Debug, PartialEq)]
+enum Search {
+    Exact,
+    Truncated,
+    Beam,
+}
+
+// A word being enumerated, its tokens so far with the context window after them. They order by probability so
+// the most probable comes out of a heap first.
+struct Candidate {
+    log_probability: f32,
+    tokens: Vec<u32>,
+    context: Vec<u32>,
+    ended: bool,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.log_probability.total_cmp(&other.log_probability)
+    }
 }
 
 #[derive(Clone)]
 pub struct Hyperparameters {
     batch_size: usize,
     block_size: usize,
-    _embedding_size: usize,
-    _hidden_size: usize,
+    embedding_size: usize,
+    hidden_size: usize,
     learn_rate: f32,
+    frozen: FrozenLayers,
+    prev_onehot: bool,
+    vocab_size: usize,
+    end: u32,
+}
+
+impl Hyperparameters {
+    // The width of the hidden layer input: the embedded context plus the optional one-hot of the last character.
+    fn input_size(&self) -> usize {
+        self.embedding_size * self.block_size + if self.prev_onehot { self.vocab_size } else { 0 }
+    }
+}
+
+// Draw a new parameter from the initialization distribution, `scale` being the max of the uniform draw from 0 or
+// the standard deviation of the normal one around 0.
+fn random_parameter<S: Into<Shape>>(distribution: InitDistribution, scale: f32, shape: S, device: &Device) -> Result<Var, VibeError> {
+    Ok(match distribution {
+        InitDistribution::Uniform => Var::rand(0f32, scale, shape, device)?,
+        InitDistribution::Normal => Var::randn(0f32, scale, shape, device)?,
+    })
+}
+
+// Text saved alongside the parameters, like the settings a model was trained with, as its UTF-8 bytes.
+fn text_tensor(text: &str) -> Result<Tensor, VibeError> {
+    Ok(Tensor::new(text.as_bytes(), &Device::Cpu)?)
+}
+
+fn tensor_text(tensor: &Tensor) -> Result<String, VibeError> {
+    String::from_utf8(tensor.to_vec1::<u8>()?).map_err(|err| VibeError::new(format!("saved text isn't valid UTF-8: {}", err)))
+}
+
+// Make sure a setting saved with a model matches the configured one. Models saved before the setting was
+// recorded are taken as they are.
+fn check_setting(saved: &HashMap<String, Tensor>, name: &str, expected: &str) -> Result<(), VibeError> {
+    let Some(tensor) = saved.get(name) else {
+        return Ok(());
+    };
+
+    let value = tensor_text(tensor)?;
+    if value != expected {
+        return Err(VibeError::new(format!(
+            "it was saved with {}={} but {}={} is configured",
+            name, value, name, expected
+        )));
+    }
+
+    Ok(())
+}
+
+// Make sure a tensor has the shape the configured sizes call for.
+fn check_shape(name: &str, tensor: &Tensor, expected: &[usize], sizes: &str) -> Result<(), VibeError> {
+    if tensor.dims() != expected {
+        return Err(VibeError::new(format!(
+            "{} has shape {:?} but the configured {} expect {:?}",
+            name,
+            tensor.dims(),
+            sizes,
+            expected
+        )));
+    }
+
+    Ok(())
 }
 
 impl Model {
     pub fn init(options: &Options) -> Result<Self, VibeError> {
         let device = device::open_device(&options.device)?;
 
-        // Tokenize the training data.
-        let data = parse::training_data(&options.data, options.block_size, &device)?;
+        // Syllables and the letters a pruned vocabulary keeps are collected from the training data up front since
+        // they decide the model's shape.
+        let vocab = match (options.tokenizer, options.prune_vocab) {
+            (Tokenizer::Char, false) => Vocab::new(Tokenizer::Char, options.end_token, &[]),
+            (tokenizer, prune) => {
+                let mut words = parse::parse_data(&options.data, options.split_on, options.encoding)?;
+                if !options.suffix.is_empty() {
+                    words = words.iter().map(|word| word.chars().rev().collect()).collect();
+                }
+                let vocab = Vocab::new(tokenizer, options.end_token, &words);
+                if prune { vocab.pruned(&words) } else { vocab }
+            }
+        };
+
+        let hyperparameters = Hyperparameters {
+            batch_size: options.batch_size,
+            block_size: options.block_size,
+            embedding_size: options.embedding_size,
+            hidden_size: options.hidden_size,
+            learn_rate: options.learn_rate,
+            frozen: options.freeze,
+            prev_onehot: options.prev_onehot,
+            vocab_size: vocab.size(),
+            end: vocab.end(),
+        };
+        let input_size = hyperparameters.input_size();
+        let vocab_size = hyperparameters.vocab_size;
+        options::check_memory(options, vocab_size)?;
+        let distribution = options.init_distribution;
+        let start_bias = start_bias(&options.start_weights, &vocab, &device)?;
 
         Ok(Self {
             model_file: options.model_file.clone(),
-            c: Var::rand(0f32, 1f32, (VOCAB_SIZE, options.embedding_size), &device)?,
+            data_file: options.data.clone(),
+            frequency_cap: options.frequency_cap,
+            c: random_parameter(distribution, 1., (vocab_size, options.embedding_size), &device)?,
             // The gain (max value) is discussed in the "Delving Deep into Rectifier" paper by Kaiming He.
-            // gain: (5/3) * sqrt(embedding_size * block_size).
-            weights_1: Var::rand(
-                0f32,
-                (5.0 / 3.0) / (options.embedding_size as f32 * options.block_size as f32).sqrt(),
-                (options.embedding_size * options.block_size, options.hidden_size),
+            // gain: (5/3) * sqrt(input_size).
+            weights_1: random_parameter(
+                distribution,
+                (5.0 / 3.0) / (input_size as f32).sqrt(),
+                (input_size, options.hidden_size),
                 &device,
             )?,
-            biases_1: Var::rand(0f32, 0.01f32, options.hidden_size, &device)?,
-            weights_2: Var::rand(0f32, 0.01f32, (options.hidden_size, VOCAB_SIZE), &device)?,
-            biases_2: Var::zeros(VOCAB_SIZE, candle_core::DType::F32, &device)?,
-            hyperparameters: Hyperparameters {
-                batch_size: options.batch_size,
-                block_size: options.block_size,
-                _embedding_size: options.embedding_size,
-                _hidden_size: options.hidden_size,
-                learn_rate: options.learn_rate,
+            biases_1: random_parameter(distribution, 0.01, options.hidden_size, &device)?,
+            weights_2: random_parameter(distribution, 0.01, (options.hidden_size, vocab_size), &device)?,
+            biases_2: Var::zeros(vocab_size, candle_core::DType::F32, &device)?,
+            hyperparameters,
+            vocab,
+            unknown_tokens: None,
+            evaluation_file: options.eval_file.clone(),
+            pretrain_file: options.pretrain_data.clone(),
+            evaluation_data: None,
+            training_data: None,
+            progress_interval: options.progress_interval,
+            loss_scale: options.units.scale(),
+            patience: options.patience,
+            val_warmup: options.val_warmup,
+            position_weights: options.position_weights,
+            autosave_every: options.autosave_every,
+            autosave_count: 0,
+            sample_checkpoints: options.sample_checkpoints,
+            loss_log: match &options.loss_log {
+                Some(path) => Some(LossLog::create(path)?),
+                None => None,
+            },
+            metrics: options.metrics_file.as_deref().map(Metrics::new),
+            sampler: sampler::from_name(&options.sampler)?,
+            target_length: options.target_length,
+            length_strength: options.length_strength,
+            padding: options.padding,
+            max_time: options.max_time,
+            start_bias,
+            start_weights: options.start_weights.clone(),
+            visualize_delay: if options.visualize_generation {
+                Some(Duration::from_millis(options.visualize_delay))
+            } else {
+                None
+            },
+            prefix: if options.suffix.is_empty() {
+                options.prefix.trim().to_lowercase()
+            } else {
+                options.suffix.trim().to_lowercase().chars().rev().collect()
             },
-            training_data: data,
+            reverse: !options.suffix.is_empty(),
+            filter: options.filter_regex.clone(),
+            ban_list: options.ban_list.clone(),
+            ema_decay: options.ema_decay,
+            ema: None,
+            generate_ema: options.generate_ema,
+            hard_mining: options.hard_mining,
+            balance_starts: options.balance_starts,
+            validation: !options.no_validation,
+            generate_seed: options.generate_seed,
+            train_seed: options.train_seed.unwrap_or_else(rand::random),
+            requested_batch_size: options.batch_size,
+            clamp_batch: options.clamp_batch,
+            label_smoothing: options.label_smoothing as f64,
+            cancel: Arc::new(AtomicBool::new(false)),
+            stop_training: Arc::new(AtomicBool::new(false)),
+            generate_parallel: options.generate_parallel,
+            embedding_init: options.embedding_init,
+            init_distribution: options.init_distribution,
+            fresh: true,
+            split_on: options.split_on,
+            encoding: options.encoding,
+            replaced_count: 0,
+            distill: options.distill,
+            distill_cap: options.distill_cap,
+            synthetic_count: 0,
+            debug_histograms: options.debug_histograms,
+            sample_every: options.sample_every,
+            debug_saturation: options.debug_saturation,
+            capitalize: options.capitalize,
+            suffix_append: options.suffix_append.clone(),
+            output: options.output.clone().map(|path| (path, options.output_format)),
+            run: 0,
             device: device,
         })
     }
 
-    // Run gradient descent backpropagation on the model parameters.
-    fn backpropagate(&mut self, loss: &Tensor) -> Result<(), VibeError> {
-        let loss_grad = loss.backward()?;
+    // Describe the active configuration as `key=value` lines.
+    pub fn describe(&self) -> String {
+        let device = if self.device.is_cuda() {
+            device::DEVICE_NAME_CUDA
+        } else if self.device.is_metal() {
+            device::DEVICE_NAME_METAL
+        } else {
+            device::DEVICE_NAME_CPU
+        };
 
-        let backpropagate_parameter = |param: &mut Var| -> Result<(), VibeError> {
-            // Clear the gradient for this parameter.
-            param.backward()?.remove(param.as_tensor());
+        let parameters: usize = [&self.c, &self.weights_1, &self.biases_1, &self.weights_2, &self.biases_2]
+            .iter()
+            .map(|param| param.elem_count())
+            .sum();
 
-            // Get the gradient from the loss gradient store.
-            let gradient = loss_grad
-                .get(param.as_tensor())
-                .ok_or_else(|| VibeError::new("missing loss gradient"))?;
+        let hyperparameters = &self.hyperparameters;
+        [
+            format!("device={}", device),
+            format!("tokenizer={}", self.vocab.tokenizer().name()),
+            format!("reverse={}", self.reverse),
+            format!("vocab_size={}", hyperparameters.vocab_size),
+            format!("end_token={}", hyperparameters.end != 0),
+            format!("block_size={}", hyperparameters.block_size),
+            format!("padding={}", self.padding.name()),
+            format!("embedding_size={}", hyperparameters.embedding_size),
+            format!("hidden_size={}", hyperparameters.hidden_size),
+            format!("prev_onehot={}", hyperparameters.prev_onehot),
+            format!("batch_size={}", hyperparameters.batch_size),
+            format!("learn_rate={}", hyperparameters.learn_rate),
+            format!("frozen={}", hyperparameters.frozen.names().join(",")),
+            format!("ema_decay={}", self.ema_decay),
+            format!("hard_mining={}", self.hard_mining),
+            format!("balance_starts={}", self.balance_starts),
+            format!("label_smoothing={}", self.label_smoothing),
+            format!("init=embeddings={}", self.embedding_init.name()),
+            format!("init_dist={}", self.init_distribution.name()),
+            format!("parameters={}", parameters),
+        ]
+        .join("\n")
+    }
 
-            // Compute the update: new_param = param - (gradient * learning_rate)
-            let updated_param =
-                param.broadcast_sub(&gradient.broadcast_mul(&Tensor::new(&[self.hyperparameters.learn_rate], &self.device)?)?)?;
+    // The stages of the network from input to output with their configured sizes.
+    pub fn layers(&self) -> Vec<Layer> {
+        let hyperparameters = &self.hyperparameters;
+        let (block_size, embedding_size) = (hyperparameters.block_size, hyperparameters.embedding_size);
+        let (input_size, hidden_size, vocab_size) = (
+            hyperparameters.input_size(),
+            hyperparameters.hidden_size,
+            hyperparameters.vocab_size,
+        );
 
-            // Replace the parameter with the updated value.
-            *param = Var::from_tensor(&updated_param)?;
+        vec![
+            Layer {
+                name: "input".to_string(),
+                size: format!("{} tokens", block_size),
+                parameters: 0,
+            },
+            Layer {
+                name: "embedding".to_string(),
+                size: if hyperparameters.prev_onehot {
+                    format!("{} x {} + {} one-hot = {}", block_size, embedding_size, vocab_size, input_size)
+                } else {
+                    format!("{} x {} = {}", block_size, embedding_size, input_size)
+                },
+                parameters: self.c.elem_count(),
+            },
+            Layer {
+                name: "hidden, tanh".to_string(),
+                size: format!("{} -> {}", input_size, hidden_size),
+                parameters: self.weights_1.elem_count() + self.biases_1.elem_count(),
+            },
+            Layer {
+                name: "output, softmax".to_string(),
+                size: format!("{} -> {}", hidden_size, vocab_size),
+                parameters: self.weights_2.elem_count() + self.biases_2.elem_count(),
+            },
+        ]
+    }
 
-            Ok(())
-        };
+    // The full distribution of the next token after a context, most likely first.
+    pub fn next_token_distribution(&self, context: &str) -> Result<Vec<(String, f32)>, VibeError> {
+        let mut window: Vec<u32> = vec![0; self.hyperparameters.block_size];
+        for (position, token) in self.vocab.encode(&context.trim().to_lowercase())?.into_iter().enumerate() {
+            tokenize::advance(&mut window, token, position, self.padding);
+        }
 
-        backpropagate_parameter(&mut self.c)?;
-        backpropagate_parameter(&mut self.weights_1)?;
-        backpropagate_parameter(&mut self.biases_1)?;
-        backpropagate_parameter(&mut self.weights_2)?;
-        backpropagate_parameter(&mut self.biases_2)?;
+        let logits = self.logits(&Tensor::new(window, &self.device)?.unsqueeze(0)?)?;
+        let mut distribution: Vec<(usize, f32)> = ops::softmax(&logits, 1)?
+            .squeeze(0)?
+            .to_vec1::<f32>()?
+            .into_iter()
+            .enumerate()
+            .collect();
+        distribution.sort_by(|a, b| b.1.total_cmp(&a.1));
 
-        Ok(())
+        Ok(distribution
+            .into_iter()
+            .map(|(index, probability)| (self.vocab.token(index as u32), probability))
+            .collect())
     }
 
-    fn forward_pass(&self, input: &Tensor, target: &Tensor) -> Result<Tensor, VibeError> {
-        // Embed the input into vectors.
-        let embeddings = self.c.index_select(&input.flatten_all()?, 0)?;
+    // The lowest loss a model could reach on the training data with this context window, in the reported units.
+    pub fn loss_floor(&self) -> Result<f32, VibeError> {
+        let data = self.data()?;
+        let contexts = data.input.to_device(&Device::Cpu)?.to_vec2::<u32>()?;
+        let targets = data.target.to_device(&Device::Cpu)?.to_vec1::<u32>()?;
 
-        // Hidden layer pre-activation with weights and biases and activation with tanh.
-        let h = embeddings
-            .reshape(((), self.weights_1.dims()[0]))?
-            .matmul(&self.weights_1)?
-            .broadcast_add(&self.biases_1)?
-            .tanh()?;
+        Ok(entropy::conditional_entropy(&contexts, &targets) * self.loss_scale)
+    }
 
-        // Output layer.
-        let logits = h.matmul(&self.weights_2)?.broadcast_add(&self.biases_2)?;
+    // Read and tokenize the training data, replacing any previously loaded data.
+    //
+    // NOTE: the data is loaded separately from init so a missing or broken data file can be reported and
+    // fixed without restarting.
+    //
+    // The vocabulary is fixed once the model is built, so a reload bringing in tokens the first load didn't
+    // have is refused rather than quietly mapping them onto other tokens.
+    pub fn load_data(&mut self) -> Result<(), VibeError> {
+        let words = self.read_words(&self.data_file)?;
+        self.load_words(words)?;
 
-        Ok(loss::cross_entropy(&logits, &target.to_dtype(candle_core::DType::U32)?)?)
+        if let Some(path) = &self.evaluation_file {
+            let words = self.read_words(path)?;
+            let (input, target, _) = tokenize::tokenize(&words, self.hyperparameters.block_size, &self.vocab, self.padding, &self.device)?;
+            self.evaluation_data = Some((input, target));
+        }
+
+        Ok(())
     }
 
-    pub fn generate(&mut self, iterations: usize, sender: &Sender<AppMessage>) -> Result<(), VibeError> {
-        for _ in 0..iterations {
-            let mut output: String = "".to_string();
-            let mut context: Vec<u8> = vec![0; self.hyperparameters.block_size];
+    // Train on the pretraining data, then swap the training data back in for fine-tuning, keeping the weights.
+    //
+    // NOTE: the pretraining data is usually a much larger generic corpus, so tokens outside the vocabulary are
+    // mapped like anything else the vocabulary can't represent instead of refusing the data as a reload would.
+    pub fn pretrain(
+        &mut self,
+        iterations: usize,
+        start: usize,
+        deadline: Option<Instant>,
+        sender: &Sender<AppMessage>,
+    ) -> Result<(), VibeError> {
+        let Some(path) = self.pretrain_file.clone() else {
+            return Ok(());
+        };
+        if iterations == 0 {
+            return Ok(());
+        }
 
-            loop {
-                let embeddings = self
-                    .c
-                    .index_select(&Tensor::new(context.clone(), &self.device)?.flatten_all()?, 0)?;
-
-                let h = embeddings
-                    .reshape(((), self.weights_1.dims()[0]))?
-                    .matmul(&self.weights_1)?
-                    .broadcast_add(&self.biases_1)?
-                    .tanh()?;
-
-                let logits = h.matmul(&self.weights_2)?.broadcast_add(&self.biases_2)?;
-
-                let probs = ops::softmax(&logits, 1)?;
-
-                // Take a random sample from the probability tensor.
-                //
-                // In order to take the probability distribution into account, a cumulative sum of the
-                // probabilities is computed and the first index with a summed probability greater than a randomly
-                // chosen value is selected.
-                let mut position: usize = 0;
-                let random_val: f32 = rand::rng().random_range(0.0..1.0);
-                let cumulative_sum = probs.cumsum(1)?.squeeze(0)?.to_vec1()?;
-                for (index, &sum) in cumulative_sum.iter().enumerate() {
-                    if random_val <= sum {
-                        position = index;
-                        break;
-                    }
-                }
+        let mut words = self.read_words(&path)?;
+        words.shuffle(&mut StdRng::seed_from_u64(self.train_seed));
+        let data = parse::training_data(
+            words,
+            self.hyperparameters.block_size,
+            &self.vocab,
+            self.padding,
+            self.frequency_cap,
+            self.validation,
+            &self.device,
+        )?;
+        self.hyperparameters.batch_size = self.batch_size_for(&data)?;
+        self.training_data = Some(data);
+        self.fresh = false;
 
-                if position == 0 {
-                    break;
-                }
-                output.push(convert::itol(position as u8));
+        // The fine-tuning data comes back even when pretraining fails, so the model never trains on the wrong set.
+        let trained = self.train_iterations(iterations, start, deadline, sender);
+        self.load_data()?;
+        trained?;
+
+        sender.send(AppMessage::Model(ModelResultMessage::FineTune {
+            run: self.run,
+            iteration: start + iterations,
+        }))?;
 
-                context.remove(0);
-                context.push(position as u8);
+        Ok(())
+    }
+
+    // When a training run starting now has to stop, if there's a time limit.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.max_time.map(|max_time| Instant::now() + max_time)
+    }
+
+    // Tokenize words already in model order as the training data.
+    pub fn load_words(&mut self, mut words: Vec<String>) -> Result<(), VibeError> {
+        let replaced_count = words.iter().map(|word| word.matches(char::REPLACEMENT_CHARACTER).count()).sum();
+
+        let unknown = self.vocab.unknown(&words);
+        if let Some(accepted) = &self.unknown_tokens {
+            let new: Vec<&str> = unknown.difference(accepted).map(|token| token.as_str()).collect();
+            if !new.is_empty() {
+                return Err(VibeError::new(format!(
+                    "{} has tokens the model's vocabulary can't represent ({}), restart to build a new model for this data",
+                    self.data_file,
+                    new.join(" ")
+                )));
             }
+        }
 
-            let _ = sender.send(AppMessage::Model(ModelResultMessage::Generated {
-                text: format!("{}", output),
-            }));
+        // Embeddings warm started from the data replace the random ones until training or a load moves them on.
+        if self.fresh && self.embedding_init == EmbeddingInit::Cooccurrence {
+            let table = cooccurrence::embeddings(&words, &self.vocab, self.hyperparameters.embedding_size)?;
+            self.c = Var::from_tensor(&Tensor::from_vec(
+                table,
+                (self.hyperparameters.vocab_size, self.hyperparameters.embedding_size),
+                &self.device,
+            )?)?;
         }
 
-        let _ = sender.send(AppMessage::Model(ModelResultMessage::Finished));
+        words.shuffle(&mut StdRng::seed_from_u64(self.train_seed));
+        let data = parse::training_data(
+            words,
+            self.hyperparameters.block_size,
+            &self.vocab,
+            self.padding,
+            self.frequency_cap,
+            self.validation,
+            &self.device,
+        )?;
+        self.hyperparameters.batch_size = self.batch_size_for(&data)?;
+        self.training_data = Some(data);
+        self.synthetic_count = 0;
+        self.replaced_count = replaced_count;
+        self.unknown_tokens.get_or_insert(unknown);
 
         Ok(())
     }
 
-    pub fn load(&mut self) -> Result<(), VibeError> {
-        let path = Path::new(&self.model_file);
+    // The batch size to train with on the given data.
+    //
+    // NOTE: batches are drawn from what's left after the validation split, so a batch larger than that would
+    // only oversample the same examples. It's an error unless clamping was asked for.
+    fn batch_size_for(&self, data: &Data) -> Result<usize, VibeError> {
+        let examples = data.input.dims()[0];
+        if examples == 0 {
+            return Err(VibeError::new(format!(
+                "{} has no training examples left after the validation split",
+                self.data_file
+            )));
+        }
+
+        if self.requested_batch_size <= examples {
+            Ok(self.requested_batch_size)
+        } else if self.clamp_batch {
+            Ok(examples)
+        } else {
+            Err(VibeError::new(format!(
+                "the batch size {} is larger than the {} training examples in {}, lower --batch-size or use --clamp-batch",
+                self.requested_batch_size, examples, self.data_file
+            )))
+        }
+    }
 
-        if path.exists() {
-            let model = safetensors::load(self.model_file.clone(), &self.device)?;
+    // Read a data file in the order the model works in.
+    fn read_words(&self, path: &String) -> Result<Vec<String>, VibeError> {
+        Ok(parse::parse_data(path, self.split_on, self.encoding)?
+            .iter()
+            .map(|word| self.orient(word))
+            .collect())
+    }
 
-            if let Some(parameter) = model.get("c") {
-                self.c = Var::from_tensor(parameter)?;
-            }
-            if let Some(parameter) = model.get("weights_1") {
-                self.weights_1 = Var::from_tensor(parameter)?;
-            }
-            if let Some(parameter) = model.get("biases_1") {
-                self.biases_1 = Var::from_tensor(parameter)?;
-            }
-            if let Some(parameter) = model.get("weights_2") {
-                self.weights_2 = Var::from_tensor(parameter)?;
-            }
-            if let Some(parameter) = model.get("biases_2") {
-                self.biases_2 = Var::from_tensor(parameter)?;
+    // Flip a word between reading order and the order the model works in, which is reversed when generating
+    // toward a suffix.
+    fn orient(&self, word: &str) -> String {
+        if self.reverse {
+            word.chars().rev().collect()
+        } else {
+            word.to_string()
+        }
+    }
+
+    // The training word closest to a generated one and how many edits apart they are, when there's one within
+    // NEAREST_DISTANCE. Ties go to the alphabetically first word so the same word always gets the same answer.
+    //
+    // NOTE: each comparison only has to beat the best distance so far, which keeps the search through every
+    // training word quick once something close turns up.
+    fn nearest(&self, word: &str) -> Option<(String, usize)> {
+        let training_words = &self.training_data.as_ref()?.training_words;
+        if training_words.contains(word) {
+            return Some((self.orient(word), 0));
+        }
+
+        let mut nearest: Option<(usize, &String)> = None;
+        for candidate in training_words {
+            let limit = nearest.map_or(NEAREST_DISTANCE, |(distance, _)| distance);
+            if let Some(distance) = distance::levenshtein(word, candidate, limit)
+                && nearest.is_none_or(|best| (distance, candidate) < best)
+            {
+                nearest = Some((distance, candidate));
             }
         }
 
-        Ok(())
+        nearest.map(|(distance, candidate)| (self.orient(candidate), distance))
     }
 
-    pub fn save(&mut self) -> Result<(), VibeError> {
-        let mut tensors: HashMap<&str, Tensor> = HashMap::new();
-        tensors.insert("c", self.c.as_tensor().clone());
-        tensors.insert("weights_1", self.weights_1.as_tensor().clone());
-        tensors.insert("biases_1", self.biases_1.as_tensor().clone());
-        tensors.insert("weights_2", self.weights_2.as_tensor().clone());
-        tensors.insert("biases_2", self.biases_2.as_tensor().clone());
+    // Whether a generated word contains any of the banned substrings, checked in reading order.
+    fn banned(&self, word: &str) -> bool {
+        let word = self.orient(word);
+        self.ban_list.iter().any(|banned| word.contains(banned.as_str()))
+    }
 
-        safetensors::save(&tensors, self.model_file.clone())?;
+    // Format a generated word for display in reading order.
+    //
+    // NOTE: this is only for showing the word, scoring and novelty checks have to use the lowercase word the
+    // model was trained on.
+    fn present(&self, word: &str) -> String {
+        let word = self.orient(word);
+        let mut letters = word.chars();
+        let mut presented = match letters.next() {
+            Some(first) if self.capitalize => first.to_uppercase().chain(letters).collect(),
+            _ => word,
+        };
+        presented.push_str(&self.suffix_append);
 
-        Ok(())
+        presented
     }
 
-    // Training rounds.
+    // The probability the model gives each character of a word, lined up with the presented word.
     //
-    // NOTE: the data is randomly batched every training round and all weights adjusted based on
-    // the batch loss. This speeds up training by not having to calculate the entire gradient every
-    // round. In the tradeoff between calculating the exact gradient every round versus running
-    // more rounds, running more rounds shows better results.
-    pub fn train(&mut self, iterations: usize, start: usize, sender: &Sender<AppMessage>) -> Result<(), VibeError> {
-        for count in start..start + iterations {
-            let batch_indices = Tensor::rand(
-                0f32,
-                self.training_data.input.dims()[0] as f32,
-                (self.hyperparameters.batch_size,),
-                &self.device,
-            )?
-            .to_dtype(candle_core::DType::U32)?;
+    // NOTE: these are the model's own probabilities, before the sampler reshapes them. A syllable's
+    // probability is shared by all of its characters.
+    fn confidence(&self, word: &str) -> Result<Vec<f32>, VibeError> {
+        let steps = self.score(word)?;
+        let mut confidence: Vec<f32> = steps[..steps.len() - 1]
+            .iter()
+            .flat_map(|step| std::iter::repeat_n(step.probability, step.token.chars().count()))
+            .collect();
+        if self.reverse {
+            confidence.reverse();
+        }
 
-            let loss = self.forward_pass(
-                &self.training_data.input.index_select(&batch_indices.flatten_all()?, 0)?,
-                &self.training_data.target.index_select(&batch_indices.flatten_all()?, 0)?,
-            )?;
+        Ok(confidence)
+    }
 
-            self.backpropagate(&loss)?;
+    // The loss on the external evaluation file, if there is one, in the reported units.
+    fn evaluation_loss(&self) -> Result<Option<f32>, VibeError> {
+        match &self.evaluation_data {
+            Some((input, target)) => Ok(Some(self.forward_pass(input, target)?.to_vec0::<f32>()? * self.loss_scale)),
+            None => Ok(None),
+        }
+    }
 
-            // Send progress updates.
-            let loss_val: f32 = loss.clone().to_device(&Device::Cpu)?.to_scalar()?;
-            let _ = sender.send(AppMessage::Model(ModelResultMessage::Progress {
-                loss_type: LossType::Training,
-                iteration: count,
-                loss: loss_val.clone(),
-            }));
+    // Summarize the loaded training data.
+    fn data_summary(&self) -> Result<String, VibeError> {
+        let data = self.data()?;
+        let mut summary = format!("loaded {} words from {}", data.word_count, self.data_file);
+        if self.encoding != Encoding::Utf8 {
+            summary.push_str(&format!(" read as {}", self.encoding.name()));
+        }
+        if data.trimmed_count > 0 {
+            summary.push_str(&format!(", trimmed {} past the frequency cap", data.trimmed_count));
+        }
+        if self.replaced_count > 0 {
+            summary.push_str(&format!(
+                ", replaced {} undecodable characters with the unknown token",
+                self.replaced_count
+            ));
+        }
+        if self.hyperparameters.batch_size < self.requested_batch_size {
+            summary.push_str(&format!(
+                ", batch size clamped to the {} training examples",
+                self.hyperparameters.batch_size
+            ));
+        }
 
-            // Send validation progress every few iterations.
-            if count % (iterations / 10) == 0 {
-                let validation_loss = self.forward_pass(&self.training_data.validation_input, &self.training_data.validation_target)?;
-                sender.send(AppMessage::Model(ModelResultMessage::Progress {
-                    loss_type: LossType::Validation,
-                    iteration: count,
-                    loss: validation_loss.to_vec0::<f32>()?,
-                }))?;
-            }
+        Ok(summary)
+    }
+
+    // The loaded training data.
+    fn data(&self) -> Result<&Data, VibeError> {
+        self.training_data
+            .as_ref()
+            .ok_or_else(|| VibeError::new(format!("no training data loaded from {}", self.data_file)))
+    }
+
+    // Copy the current parameters so they can be restored later.
+    fn snapshot(&self) -> Result<Parameters, VibeError> {
+        Ok(Parameters {
+            c: self.c.as_tensor().copy()?,
+            weights_1: self.weights_1.as_tensor().copy()?,
+            biases_1: self.biases_1.as_tensor().copy()?,
+            weights_2: self.weights_2.as_tensor().copy()?,
+            biases_2: self.biases_2.as_tensor().copy()?,
+        })
+    }
+
+    // Replace the current parameters with a snapshot.
+    fn restore(&mut self, parameters: &Parameters) -> Result<(), VibeError> {
+        self.c = Var::from_tensor(&parameters.c)?;
+        self.weights_1 = Var::from_tensor(&parameters.weights_1)?;
+        self.biases_1 = Var::from_tensor(&parameters.biases_1)?;
+        self.weights_2 = Var::from_tensor(&parameters.weights_2)?;
+        self.biases_2 = Var::from_tensor(&parameters.biases_2)?;
+
+        Ok(())
+    }
+
+    // Move the parameters and the data to another device. Everything is copied over before any of it is
+    // replaced, so a device that can't be opened or runs out of memory leaves the model where it was.
+    pub fn switch_device(&mut self, name: &String) -> Result<(), VibeError> {
+        let device = device::open_device(name)?;
+
+        let parameters = self.snapshot()?.to_device(&device)?;
+        let ema = self.ema.as_ref().map(|ema| ema.to_device(&device)).transpose()?;
+        let training_data = self.training_data.as_ref().map(|data| data.to_device(&device)).transpose()?;
+        let evaluation_data = match &self.evaluation_data {
+            Some((input, target)) => Some((input.to_device(&device)?, target.to_device(&device)?)),
+            None => None,
+        };
+        let start_bias = self.start_bias.as_ref().map(|bias| bias.to_device(&device)).transpose()?;
+
+        self.restore(&parameters)?;
+        self.ema = ema;
+        self.training_data = training_data;
+        self.evaluation_data = evaluation_data;
+        self.start_bias = start_bias;
+        self.device = device;
+
+        Ok(())
+    }
+
+    // Fold the current parameters into the moving average, starting it from them on the first step.
+    fn update_ema(&mut self) -> Result<(), VibeError> {
+        if self.ema_decay == 0. {
+            return Ok(());
         }
 
-        sender.send(AppMessage::Model(ModelResultMessage::Finished))?;
+        self.ema = Some(match self.ema.take() {
+            Some(ema) => ema.blend(&self.snapshot()?, self.ema_decay as f64)?,
+            None => self.snapshot()?,
+        });
 
         Ok(())
     }
-}
 
-// Main event loop for the model thread.
-pub fn run_model(commands: Receiver<ModelCommandMessage>, results: Sender<AppMessage>, options: &Options) -> Result<(), VibeError> {
-    let mut model = Model::init(options)?;
-    model.load()?;
+    // Run gradient descent backpropagation on the model parameters, histogramming the gradients of the updated
+    // parameters if asked.
+    fn backpropagate(&mut self, loss: &Tensor, histograms: bool) -> Result<Vec<Histogram>, VibeError> {
+        let loss_grad = loss.backward()?;
+        let mut gradients = Vec::new();
 
-    loop {
-        match commands.recv() {
-            Ok(ModelCommandMessage::Train { iterations, start }) => {
-                model.train(iterations, start, &results).unwrap_or_else(|err| {
-                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { err: err }));
-                });
-            }
+        let mut backpropagate_parameter = |name: &str, param: &mut Var| -> Result<(), VibeError> {
+            // Clear the gradient for this parameter.
+            param.backward()?.remove(param.as_tensor());
 
-            Ok(ModelCommandMessage::Vibe { count }) => {
-                model.generate(count, &results).unwrap_or_else(|err| {
-                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { err: err }));
-                });
+            // Get the gradient from the loss gradient store.
+            let gradient = loss_grad
+                .get(param.as_tensor())
+                .ok_or_else(|| VibeError::new("missing loss gradient"))?;
+            if histograms {
+                gradients.push(histogram(name, gradient)?);
             }
 
-            Ok(ModelCommandMessage::Save) => {
-                model.save()?;
-            }
+            // Compute the update: new_param = param - (gradient * learning_rate)
+            let updated_param =
+                param.broadcast_sub(&gradient.broadcast_mul(&Tensor::new(&[self.hyperparameters.learn_rate], &self.device)?)?)?;
 
-            Ok(ModelCommandMessage::Shutdown) => {
-                break;
-            }
+            // Replace the parameter with the updated value.
+            *param = Var::from_tensor(&updated_param)?;
 
-            Err(_) => {
-                break;
-            }
+            Ok(())
+        };
+
+        let frozen = self.hyperparameters.frozen;
+        if !frozen.embeddings {
+            backpropagate_parameter(PARAMETER_NAMES[0], &mut self.c)?;
+        }
+        if !frozen.hidden {
+            backpropagate_parameter(PARAMETER_NAMES[1], &mut self.weights_1)?;
+            backpropagate_parameter(PARAMETER_NAMES[2], &mut self.biases_1)?;
+        }
+        if !frozen.output {
+            backpropagate_parameter(PARAMETER_NAMES[3], &mut self.weights_2)?;
+            backpropagate_parameter(PARAMETER_NAMES[4], &mut self.biases_2)?;
         }
+
+        Ok(gradients)
+    }
+
+    // Histogram the current value of every parameter.
+    fn weight_histograms(&self) -> Result<Vec<Histogram>, VibeError> {
+        [&self.c, &self.weights_1, &self.biases_1, &self.weights_2, &self.biases_2]
+            .iter()
+            .zip(PARAMETER_NAMES)
+            .map(|(param, name)| histogram(name, param.as_tensor()))
+            .collect()
+    }
+
+    // Build the hidden layer input for a batch of contexts with shape (batch, block_size).
+    fn embed(&self, contexts: &Tensor) -> Result<Tensor, VibeError> {
+        // Embed the input into vectors, one row of concatenated character embeddings per context.
+        let embeddings = self
+            .c
+            .index_select(&contexts.flatten_all()?, 0)?
+            .reshape((contexts.dims()[0], ()))?;
+
+        if !self.hyperparameters.prev_onehot {
+            return Ok(embeddings);
+        }
+
+        // Append the one-hot encoding of the most recent character in each context.
+        let last = contexts
+            .narrow(1, self.hyperparameters.block_size - 1, 1)?
+            .flatten_all()?
+            .to_dtype(candle_core::DType::U32)?;
+        let onehot = Tensor::eye(self.hyperparameters.vocab_size, candle_core::DType::F32, &self.device)?.index_select(&last, 0)?;
+
+        Ok(Tensor::cat(&[&embeddings, &onehot], 1)?)
+    }
+
+    // Compute the hidden layer activations for a batch of contexts with shape (batch, block_size).
+    fn hidden(&self, contexts: &Tensor) -> Result<Tensor, VibeError> {
+        let hyperparameters = &self.hyperparameters;
+
+        let embeddings = self.embed(contexts)?;
+        check_shape(
+            "embedded input",
+            &embeddings,
+            &[contexts.dims()[0], hyperparameters.input_size()],
+            "embedding_size * block_size",
+        )?;
+
+        // Hidden layer pre-activation with weights and biases and activation with tanh.
+        let h = embeddings.matmul(&self.weights_1)?.broadcast_add(&self.biases_1)?.tanh()?;
+        check_shape(
+            "hidden layer",
+            &h,
+            &[contexts.dims()[0], hyperparameters.hidden_size],
+            "hidden_size",
+        )?;
+
+        Ok(h)
+    }
+
+    // Compute the output logits for a batch of contexts with shape (batch, block_size).
+    fn logits(&self, contexts: &Tensor) -> Result<Tensor, VibeError> {
+        let h = self.hidden(contexts)?;
+
+        // Output layer.
+        Ok(h.matmul(&self.weights_2)?.broadcast_add(&self.biases_2)?)
+    }
+
+    // The fraction of hidden activations for a batch of contexts that are saturated.
+    //
+    // NOTE: the init keeps the pre-activations small so tanh starts out in its linear regime, a large share of
+    // saturated units means the init gain or the learning rate has pushed them out of it.
+    fn saturation(&self, contexts: &Tensor) -> Result<f32, VibeError> {
+        let saturated = self.hidden(contexts)?.abs()?.gt(SATURATION_THRESHOLD)?;
+
+        Ok(saturated.to_dtype(candle_core::DType::F32)?.mean_all()?.to_scalar::<f32>()?)
+    }
+
+    fn forward_pass(&self, input: &Tensor, target: &Tensor) -> Result<Tensor, VibeError> {
+        let logits = self.logits(input)?;
+
+        Ok(loss::cross_entropy(&logits, &target.to_dtype(candle_core::DType::U32)?)?)
+    }
+
+    // The loss training minimizes, cross entropy against targets smoothed toward the uniform distribution,
+    // averaged with the given per-example weights if there are any.
+    //
+    // NOTE: candle's cross entropy only takes hard targets, but the smoothed target's cross entropy splits into
+    // the hard target's plus the mean over the whole vocabulary, weighted by the smoothing. It also only reduces
+    // to a plain mean, so weighted losses are taken per example and reduced here.
+    fn training_loss(&self, input: &Tensor, target: &Tensor, weights: Option<&Tensor>) -> Result<Tensor, VibeError> {
+        if self.label_smoothing == 0. && weights.is_none() {
+            return self.forward_pass(input, target);
+        }
+
+        let log_probs = ops::log_softmax(&self.logits(input)?, 1)?;
+        let Some(weights) = weights else {
+            let hard = loss::nll(&log_probs, &target.to_dtype(candle_core::DType::U32)?)?;
+            let uniform = log_probs.mean(1)?.mean_all()?.neg()?;
+
+            return Ok(((hard * (1. - self.label_smoothing))? + (uniform * self.label_smoothing)?)?);
+        };
+
+        let hard = log_probs
+            .gather(&target.to_dtype(candle_core::DType::U32)?.unsqueeze(1)?, 1)?
+            .neg()?
+            .flatten_all()?;
+        let uniform = log_probs.mean(1)?.neg()?;
+        let losses = ((hard * (1. - self.label_smoothing))? + (uniform * self.label_smoothing)?)?;
+
+        Ok((losses * weights)?.sum_all()?.broadcast_div(&weights.sum_all()?)?)
+    }
+
+    // The weight of each training example's loss by the position of its target, none when every position
+    // counts the same.
+    //
+    // NOTE: a target at position p has block_size - p padding tokens in its context, linear weights ramp up
+    // over those so the full weight starts at the first position whose context is all real tokens.
+    fn loss_weights(&self) -> Result<Option<Tensor>, VibeError> {
+        let positions = &self.data()?.positions;
+        let full = self.hyperparameters.block_size + 1;
+
+        match self.position_weights {
+            PositionWeights::Uniform => Ok(None),
+            PositionWeights::Linear => Ok(Some(Tensor::from_vec(
+                positions
+                    .iter()
+                    .map(|position| (position + 1).min(full) as f32 / full as f32)
+                    .collect::<Vec<f32>>(),
+                positions.len(),
+                &self.device,
+            )?)),
+        }
+    }
+
+    // The average validation loss of the targets at each position within their words, with how many targets
+    // there were at that position.
+    pub fn position_losses(&self) -> Result<Vec<PositionLoss>, VibeError> {
+        if !self.validation {
+            return Err(VibeError::new(
+                "there's no validation data to measure position losses on with --no-validation",
+            ));
+        }
+
+        let data = self.data()?;
+        let log_probs = ops::log_softmax(&self.logits(&data.validation_input)?, 1)?;
+        let losses = log_probs
+            .gather(&data.validation_target.to_dtype(candle_core::DType::U32)?.unsqueeze(1)?, 1)?
+            .neg()?
+            .flatten_all()?
+            .to_vec1::<f32>()?;
+
+        let mut positions: Vec<PositionLoss> = Vec::new();
+        for (position, loss) in data.validation_positions.iter().zip(losses) {
+            if positions.len() <= *position {
+                positions.resize(position + 1, PositionLoss { loss: 0., count: 0 });
+            }
+            positions[*position].loss += loss;
+            positions[*position].count += 1;
+        }
+
+        for position in positions.iter_mut().filter(|position| position.count > 0) {
+            position.loss = position.loss / position.count as f32 * self.loss_scale;
+        }
+
+        Ok(positions)
+    }
+
+    // Sampling weights over the training examples. With hard mining each example is weighted by its own loss
+    // from a full forward pass, and with balanced starts the word starts are reweighted by their first token.
+    fn example_weights(&self) -> Result<WeightedIndex<f32>, VibeError> {
+        let data = self.data()?;
+        let mut weights = if self.hard_mining {
+            let log_probs = ops::log_softmax(&self.logits(&data.input)?, 1)?;
+            log_probs
+                .gather(&data.target.to_dtype(candle_core::DType::U32)?.unsqueeze(1)?, 1)?
+                .neg()?
+                .flatten_all()?
+                .to_vec1::<f32>()?
+        } else {
+            vec![1.; data.positions.len()]
+        };
+
+        if self.balance_starts {
+            for (weight, balance) in weights.iter_mut().zip(self.start_balance()?) {
+                *weight *= balance;
+            }
+        }
+
+        WeightedIndex::new(weights).map_err(|e| VibeError::new(format!("unable to weight the training examples: {}", e)))
+    }
+
+    // A weight for each training example that evens out how often each first token starts a word.
+    //
+    // NOTE: every first token gets the same share of the word starts, so a start with a rare first letter is
+    // weighted up and one with a common first letter down while the starts as a whole keep their weight.
+    // Examples past the start are left alone.
+    fn start_balance(&self) -> Result<Vec<f32>, VibeError> {
+        let data = self.data()?;
+        let targets = data.target.to_vec1::<u32>()?;
+
+        let mut starts: HashMap<u32, usize> = HashMap::new();
+        for (target, position) in targets.iter().zip(&data.positions) {
+            if *position == 0 {
+                *starts.entry(*target).or_insert(0) += 1;
+            }
+        }
+
+        let share = starts.values().sum::<usize>() as f32 / starts.len().max(1) as f32;
+        Ok(targets
+            .iter()
+            .zip(&data.positions)
+            .map(|(target, position)| if *position == 0 { share / starts[target] as f32 } else { 1. })
+            .collect())
+    }
+
+    // Sample a single word from the model.
+    //
+    // When visualizing, each step's distribution is sent to the UI and generation is slowed down to watch it.
+    fn sample_word(&self, prefix: &str, rng: &mut dyn RngCore, sender: Option<&Sender<AppMessage>>) -> Result<String, VibeError> {
+        let (mut output, tokens) = self.opening(prefix, rng)?;
+        let mut context: Vec<u32> = vec![0; self.hyperparameters.block_size];
+        for (position, &token) in tokens.iter().enumerate() {
+            tokenize::advance(&mut context, token, position, self.padding);
+        }
+        let mut length = tokens.len();
+
+        loop {
+            let mut logits = self.logits(&Tensor::new(context.clone(), &self.device)?.unsqueeze(0)?)?;
+
+            if let Some(bias) = self.length_bias(output.chars().count())? {
+                logits = logits.broadcast_add(&bias)?;
+            }
+            if output.is_empty()
+                && let Some(bias) = &self.start_bias
+            {
+                logits = logits.broadcast_add(bias)?;
+            }
+
+            let probs = ops::softmax(&logits, 1)?;
+
+            if let (Some(delay), Some(sender)) = (self.visualize_delay, sender) {
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::GenerationStep {
+                    step: GenerationStep {
+                        context: self.vocab.display(&context),
+                        output: output.clone(),
+                        candidates: self.step_candidates(&probs)?,
+                    },
+                }));
+                thread::sleep(delay);
+            }
+
+            let position = self.sampler.choose(&probs, rng)?;
+
+            // The '.' delimiter also ends the word when there's a separate end token, it's only ever
+            // sampled from a poorly trained model.
+            if position == 0 || position == self.hyperparameters.end as usize {
+                break;
+            }
+            output.push_str(&self.vocab.token(position as u32));
+
+            tokenize::advance(&mut context, position as u32, length, self.padding);
+            length += 1;
+        }
+
+        Ok(output)
+    }
+
+    // The text and tokens a word starts from, the prefix unless the model was trained without padding and the
+    // prefix is too short to fill a context. Those start from the opening of a random training word that
+    // begins with the prefix instead, since the model never learned to start from less.
+    fn opening(&self, prefix: &str, rng: &mut dyn RngCore) -> Result<(String, Vec<u32>), VibeError> {
+        let tokens = self.vocab.encode(prefix)?;
+        let block_size = self.hyperparameters.block_size;
+        if self.padding != Padding::None || tokens.len() >= block_size {
+            return Ok((prefix.to_string(), tokens));
+        }
+
+        let openings: Vec<Vec<u32>> = self
+            .data()?
+            .training_words
+            .iter()
+            .filter(|word| word.starts_with(prefix))
+            .filter_map(|word| self.vocab.encode(word).ok())
+            .filter(|tokens| tokens.len() >= block_size)
+            .map(|tokens| tokens[..block_size].to_vec())
+            .collect();
+        let tokens = openings.choose(rng).cloned().ok_or_else(|| {
+            VibeError::new(format!(
+                "no training word starting with '{}' is long enough to open a word without padding",
+                prefix
+            ))
+        })?;
+
+        Ok((tokens.iter().map(|&token| self.vocab.token(token)).collect(), tokens))
+    }
+
+    // The tokens shown for a generation step, the whole vocabulary when it's small enough or else the most
+    // likely tokens, in vocabulary order.
+    fn step_candidates(&self, probs: &Tensor) -> Result<Vec<(String, f32)>, VibeError> {
+        let mut candidates: Vec<(usize, f32)> = probs.squeeze(0)?.to_vec1::<f32>()?.into_iter().enumerate().collect();
+        if candidates.len() > STEP_CANDIDATES {
+            candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+            candidates.truncate(STEP_CANDIDATES);
+            candidates.sort_by_key(|candidate| candidate.0);
+        }
+
+        Ok(candidates
+            .into_iter()
+            .map(|(index, probability)| (self.vocab.token(index as u32), probability))
+            .collect())
+    }
+
+    // Sample a word starting from the configured prefix. If the model gives up on the prefix straight away,
+    // retry from progressively shorter prefixes until one continues. Returns the word and the prefix length
+    // that was used.
+    fn sample_prefixed(&self, rng: &mut dyn RngCore, sender: Option<&Sender<AppMessage>>) -> Result<(String, usize), VibeError> {
+        self.sample_prefixed_from(self.prefix.chars().count(), rng, sender)
+    }
+
+    // Sample a word starting from at most the first `longest` characters of the prefix.
+    fn sample_prefixed_from(
+        &self,
+        longest: usize,
+        rng: &mut dyn RngCore,
+        sender: Option<&Sender<AppMessage>>,
+    ) -> Result<(String, usize), VibeError> {
+        let prefix: Vec<char> = self.prefix.chars().collect();
+        for length in (1..=longest).rev() {
+            let start: String = prefix[..length].iter().collect();
+
+            // A prefix with syllables outside the vocabulary can't be continued at all.
+            if let Ok(output) = self.sample_word(&start, rng, sender)
+                && output.chars().count().saturating_sub(length) > 1
+            {
+                return Ok((output, length));
+            }
+        }
+
+        Ok((self.sample_word("", rng, sender)?, 0))
+    }
+
+    // The generator for the word at an index. A pinned seed gives each word its own generator, so the same
+    // seed reproduces the same list.
+    fn word_rng(&self, index: usize) -> Box<dyn RngCore> {
+        match self.generate_seed {
+            Some(seed) => Box::new(StdRng::seed_from_u64(seed.wrapping_add(index as u64))),
+            None => Box::new(rand::rng()),
+        }
+    }
+
+    // Sample the words at a range of indices together, advancing all of their contexts through one forward
+    // pass per step and retiring each as it ends. Every word draws from its own generator in the same order
+    // the sequential path would, so the words come out the same.
+    //
+    // NOTE: only the full prefix is sampled in parallel, a word that can't continue it falls back to the
+    // sequential path with the shorter prefixes. A prefix the vocabulary can't encode gives no drafts at all
+    // and leaves everything to the sequential path.
+    fn sample_batch(&self, indices: Range<usize>) -> Result<VecDeque<Draft>, VibeError> {
+        let mut rngs: Vec<Box<dyn RngCore>> = indices.map(|index| self.word_rng(index)).collect();
+        let Ok(prefix_tokens) = self.vocab.encode(&self.prefix) else {
+            return Ok(VecDeque::new());
+        };
+
+        // Without padding every word picks its own opening.
+        let block_size = self.hyperparameters.block_size;
+        if self.padding == Padding::None && prefix_tokens.len() < block_size {
+            return Ok(VecDeque::new());
+        }
+
+        let mut context: Vec<u32> = vec![0; block_size];
+        for (position, &token) in prefix_tokens.iter().enumerate() {
+            tokenize::advance(&mut context, token, position, self.padding);
+        }
+        let mut length = prefix_tokens.len();
+
+        let mut contexts: Vec<Vec<u32>> = vec![context; rngs.len()];
+        let mut outputs: Vec<String> = vec![self.prefix.clone(); rngs.len()];
+        let mut active: Vec<usize> = (0..rngs.len()).collect();
+
+        while !active.is_empty() {
+            let input: Vec<u32> = active.iter().flat_map(|&word| contexts[word].iter().copied()).collect();
+            let mut logits = self.logits(&Tensor::from_vec(input, (active.len(), block_size), &self.device)?)?;
+
+            if self.target_length > 0 {
+                let mut biases: Vec<Tensor> = Vec::new();
+                for &word in &active {
+                    if let Some(bias) = self.length_bias(outputs[word].chars().count())? {
+                        biases.push(bias);
+                    }
+                }
+                logits = logits.add(&Tensor::cat(&biases, 0)?)?;
+            }
+
+            // Every word in the batch is at the same step, so they all start together.
+            if outputs[active[0]].is_empty()
+                && let Some(bias) = &self.start_bias
+            {
+                logits = logits.broadcast_add(bias)?;
+            }
+
+            // The samplers read the probabilities on the host anyway, copy them over once for the whole batch.
+            let probs = ops::softmax(&logits, 1)?.to_device(&Device::Cpu)?;
+
+            let mut continuing: Vec<usize> = Vec::new();
+            for (row, &word) in active.iter().enumerate() {
+                let position = self.sampler.choose(&probs.get(row)?.unsqueeze(0)?, &mut *rngs[word])?;
+                if position == 0 || position == self.hyperparameters.end as usize {
+                    continue;
+                }
+                outputs[word].push_str(&self.vocab.token(position as u32));
+
+                tokenize::advance(&mut contexts[word], position as u32, length, self.padding);
+                continuing.push(word);
+            }
+            active = continuing;
+            length += 1;
+        }
+
+        let prefix_length = self.prefix.chars().count();
+        let mut drafts: VecDeque<Draft> = VecDeque::new();
+        for (mut rng, output) in rngs.into_iter().zip(outputs) {
+            let draft = if prefix_length == 0 || output.chars().count().saturating_sub(prefix_length) > 1 {
+                (output, prefix_length)
+            } else {
+                self.sample_prefixed_from(prefix_length - 1, &mut *rng, None)?
+            };
+            drafts.push_back((rng, draft));
+        }
+
+        Ok(drafts)
+    }
+
+    // Logit bias nudging words toward the target length, by pushing the end token down while the word is
+    // shorter than the target and up once it's longer. The length is counted in characters, like the target.
+    fn length_bias(&self, length: usize) -> Result<Option<Tensor>, VibeError> {
+        if self.target_length == 0 {
+            return Ok(None);
+        }
+
+        let mut bias = vec![0f32; self.hyperparameters.vocab_size];
+        bias[self.hyperparameters.end as usize] = self.length_strength * (length as f32 - self.target_length as f32);
+
+        Ok(Some(Tensor::from_vec(bias, (1, self.hyperparameters.vocab_size), &self.device)?))
+    }
+
+    // Generate a single word tagged with where it came from.
+    fn send_sample(&self, tag: &str, sender: &Sender<AppMessage>) -> Result<(), VibeError> {
+        let (text, _) = self.sample_prefixed(&mut rand::rng(), None)?;
+        let _ = sender.send(AppMessage::Model(ModelResultMessage::Generated {
+            text: self.present(&text),
+            tag: Some(tag.to_string()),
+            confidence: self.confidence(&text)?,
+            nearest: self.nearest(&text),
+        }));
+
+        Ok(())
+    }
+
+    // Generate from the averaged weights when asked and there are some, putting the trained weights back after.
+    pub fn generate(&mut self, iterations: usize, sender: &Sender<AppMessage>) -> Result<(), VibeError> {
+        // A cancel that arrived after the last generation finished is stale.
+        self.cancel.store(false, Ordering::Relaxed);
+
+        if !self.generate_ema {
+            return self.generate_words(iterations, sender);
+        }
+
+        let Some(ema) = self.ema.take() else {
+            return self.generate_words(iterations, sender);
+        };
+
+        let trained = self.snapshot()?;
+        self.restore(&ema)?;
+        let result = self.generate_words(iterations, sender);
+        self.restore(&trained)?;
+        self.ema = Some(ema);
+
+        result
+    }
+
+    fn generate_words(&mut self, iterations: usize, sender: &Sender<AppMessage>) -> Result<(), VibeError> {
+        let mut unique: HashSet<String> = HashSet::new();
+        let training_words = self.training_data.as_ref().map(|data| &data.training_words);
+        let mut novel = 0;
+        let mut novel_likelihood = 0.;
+        let mut distilled: Vec<String> = Vec::new();
+        let mut written: Vec<OutputWord> = Vec::new();
+
+        let mut rejected = 0;
+        let mut banned = 0;
+        let mut count = 0;
+        let mut empty = 0;
+
+        // Words sampled ahead together when generating in parallel, each with the generator it was drawn with.
+        let mut drafts: VecDeque<Draft> = VecDeque::new();
+
+        for index in 0..iterations {
+            // Stop between words when cancelled, keeping what was already generated.
+            if self.cancel.load(Ordering::Relaxed) {
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::Status {
+                    run: self.run,
+                    text: format!("generation cancelled after {} of {} words", count, iterations),
+                }));
+                break;
+            }
+
+            // Stepping through the sampling one word at a time can't be done in parallel.
+            if self.generate_parallel > 1 && self.visualize_delay.is_none() && drafts.is_empty() {
+                drafts = self.sample_batch(index..(index + self.generate_parallel).min(iterations))?;
+            }
+
+            let (mut rng, (mut output, mut prefix_length)) = match drafts.pop_front() {
+                Some(draft) => draft,
+                None => {
+                    let mut rng = self.word_rng(index);
+                    let sample = self.sample_prefixed(&mut *rng, Some(sender))?;
+                    (rng, sample)
+                }
+            };
+
+            // Redraw words the filter or the ban list rejects, giving up after enough attempts. The last word is
+            // kept when it only fails the filter, but a word that's still banned is dropped.
+            let mut filtered = true;
+            let mut attempts = 1;
+            loop {
+                let allowed = !self.banned(&output);
+                let matched = self.filter.as_ref().is_none_or(|filter| filter.is_match(&self.orient(&output)));
+                if allowed && matched {
+                    break;
+                }
+
+                if allowed {
+                    rejected += 1;
+                } else {
+                    banned += 1;
+                }
+                if attempts == FILTER_ATTEMPTS {
+                    filtered = false;
+                    break;
+                }
+                (output, prefix_length) = self.sample_prefixed(&mut *rng, Some(sender))?;
+                attempts += 1;
+            }
+            if self.banned(&output) {
+                continue;
+            }
+
+            let novel_word = training_words.map(|words| !words.contains(&output));
+            if self.output.is_some() {
+                written.push(OutputWord {
+                    word: self.present(&output),
+                    likelihood: self.likelihood(&output)?,
+                    novel: novel_word,
+                });
+            }
+
+            if novel_word == Some(true) {
+                novel += 1;
+                let likelihood = self.likelihood(&output)?;
+                novel_likelihood += likelihood;
+                if self.distill.is_some_and(|threshold| likelihood >= threshold) && !distilled.contains(&output) {
+                    distilled.push(output.clone());
+                }
+            }
+
+            if output.chars().count() == prefix_length {
+                empty += 1;
+            }
+
+            unique.insert(output.clone());
+            let _ = sender.send(AppMessage::Model(ModelResultMessage::Generated {
+                text: self.present(&output),
+                tag: if !filtered {
+                    Some("unfiltered".to_string())
+                } else if prefix_length < self.prefix.chars().count() {
+                    Some(format!("{} {}", if self.reverse { "suffix" } else { "prefix" }, prefix_length))
+                } else {
+                    None
+                },
+                confidence: self.confidence(&output)?,
+                nearest: self.nearest(&output),
+            }));
+            count += 1;
+        }
+
+        // An untrained model tends to end every word right away, say so rather than leave a list of blanks.
+        if count > 0 && empty == count {
+            let _ = sender.send(AppMessage::Model(ModelResultMessage::Status {
+                run: self.run,
+                text: if self.prefix.is_empty() {
+                    "the model only produced empty strings, train more first".to_string()
+                } else {
+                    "the model produced nothing past the prefix, train more first".to_string()
+                },
+            }));
+        }
+
+        let _ = sender.send(AppMessage::Model(ModelResultMessage::GenerationSummary {
+            summary: GenerationSummary {
+                unique: unique.len(),
+                count,
+                novel: training_words.map(|_| novel),
+                rejected,
+                banned,
+                likelihood: if novel > 0 { novel_likelihood / novel as f32 } else { 0. },
+            },
+        }));
+
+        if let Some((path, format)) = &self.output {
+            output::write(path, *format, &written)?;
+        }
+
+        if self.distill.is_some() {
+            let text = self.distill_words(distilled)?;
+            let _ = sender.send(AppMessage::Model(ModelResultMessage::Status { run: self.run, text }));
+        }
+
+        let _ = sender.send(AppMessage::Model(ModelResultMessage::Finished));
+
+        Ok(())
+    }
+
+    // Add generated words to the training data for the next round of training, up to the cap on how many can be
+    // added relative to the real words so the model doesn't end up mostly training on itself.
+    //
+    // NOTE: the words join the training words too, so the same word isn't novel and added again later. A reload
+    // drops all of them.
+    fn distill_words(&mut self, mut words: Vec<String>) -> Result<String, VibeError> {
+        let data = self.data()?;
+        let limit = (data.word_count as f32 * self.distill_cap) as usize;
+        words.truncate(limit.saturating_sub(self.synthetic_count));
+        if words.is_empty() {
+            return Ok(format!(
+                "no generated words added to the training data, {} of at most {} already added",
+                self.synthetic_count, limit
+            ));
+        }
+
+        let (input, target, positions) =
+            tokenize::tokenize(&words, self.hyperparameters.block_size, &self.vocab, self.padding, &self.device)?;
+        let data = self
+            .training_data
+            .as_mut()
+            .ok_or_else(|| VibeError::new("no training data to add to"))?;
+        data.input = Tensor::cat(&[&data.input, &input], 0)?;
+        data.target = Tensor::cat(&[&data.target, &target], 0)?;
+        data.positions.extend(positions);
+        data.training_words.extend(words.iter().cloned());
+        self.synthetic_count += words.len();
+
+        Ok(format!(
+            "added {} generated words to the training data, {} of at most {}",
+            words.len(),
+            self.synthetic_count,
+            limit
+        ))
+    }
+
+    // Sample a few words and find the character that makes up most of them, if one does.
+    //
+    // NOTE: a data file full of characters outside the vocabulary collapses them all onto one token, and the
+    // model happily learns to output little else.
+    fn collapsed_letter(&self) -> Result<Option<(char, f32)>, VibeError> {
+        let mut counts: HashMap<char, usize> = HashMap::new();
+        let mut rng = rand::rng();
+        for _ in 0..COLLAPSE_SAMPLES {
+            let (word, prefix_length) = self.sample_prefixed(&mut rng, None)?;
+            for letter in word.chars().skip(prefix_length) {
+                *counts.entry(letter).or_insert(0) += 1;
+            }
+        }
+
+        let total: usize = counts.values().sum();
+        Ok(counts
+            .into_iter()
+            .max_by_key(|(_, count)| *count)
+            .map(|(letter, count)| (letter, count as f32 / total as f32))
+            .filter(|(_, share)| *share > COLLAPSE_SHARE))
+    }
+
+    // The probability of each next token after a context, cached since enumerating revisits the same contexts.
+    //
+    // NOTE: these are the model's own probabilities, without the length and start biases or the sampler.
+    fn next_probabilities(&self, context: &[u32], cache: &mut HashMap<Vec<u32>, Vec<f32>>) -> Result<Vec<f32>, VibeError> {
+        if let Some(probs) = cache.get(context) {
+            return Ok(probs.clone());
+        }
+
+        let logits = self.logits(&Tensor::new(context, &self.device)?.unsqueeze(0)?)?;
+        let probs = ops::softmax(&logits, 1)?.squeeze(0)?.to_vec1::<f32>()?;
+        cache.insert(context.to_vec(), probs.clone());
+
+        Ok(probs)
+    }
+
+    // Every way of continuing a word by one token.
+    fn extend(&self, candidate: &Candidate, cache: &mut HashMap<Vec<u32>, Vec<f32>>) -> Result<Vec<Candidate>, VibeError> {
+        let probs = self.next_probabilities(&candidate.context, cache)?;
+
+        Ok(probs
+            .iter()
+            .enumerate()
+            .filter(|(_, probability)| **probability > 0.)
+            .map(|(token, probability)| {
+                let token = token as u32;
+                let ended = token == 0 || token == self.hyperparameters.end;
+                let mut tokens = candidate.tokens.clone();
+                let mut context = candidate.context.clone();
+                if !ended {
+                    tokenize::advance(&mut context, token, tokens.len(), self.padding);
+                    tokens.push(token);
+                }
+
+                Candidate {
+                    log_probability: candidate.log_probability + probability.ln(),
+                    tokens,
+                    context,
+                    ended,
+                }
+            })
+            .collect())
+    }
+
+    // The complete words with the highest total probability starting from the prefix, most probable first,
+    // along with how they were searched for.
+    //
+    // Small block sizes have few enough contexts to search best first, where words come out of the heap in
+    // order of probability since extending a word only makes it less likely. Larger ones keep the most probable
+    // partial words at each length, which can miss words whose start is unlikely.
+    fn enumerate(&self, count: usize) -> Result<(Vec<(String, f32)>, Search), VibeError> {
+        let tokens = self.vocab.encode(&self.prefix)?;
+        let block_size = self.hyperparameters.block_size;
+        if self.padding == Padding::None && tokens.len() < block_size {
+            return Err(VibeError::new(
+                "the model was trained without padding, enumerating needs a prefix at least a block long",
+            ));
+        }
+
+        let mut context: Vec<u32> = vec![0; block_size];
+        for (position, &token) in tokens.iter().enumerate() {
+            tokenize::advance(&mut context, token, position, self.padding);
+        }
+        let start = Candidate {
+            log_probability: 0.,
+            tokens,
+            context,
+            ended: false,
+        };
+
+        let mut cache: HashMap<Vec<u32>, Vec<f32>> = HashMap::new();
+        let mut words: Vec<Candidate> = Vec::new();
+        let search = if block_size <= ENUMERATE_BLOCK_SIZE {
+            // A partial word dropped at a limit was more probable than anything found after it, which it or its
+            // endings could have beaten.
+            let mut search = Search::Exact;
+            let mut heap: BinaryHeap<Candidate> = BinaryHeap::from([start]);
+            let mut expansions = 0;
+            while let Some(candidate) = heap.pop() {
+                if candidate.ended {
+                    words.push(candidate);
+                    if words.len() == count {
+                        break;
+                    }
+                } else if candidate.tokens.len() < ENUMERATE_MAX_LENGTH && expansions < ENUMERATE_EXPANSIONS {
+                    heap.extend(self.extend(&candidate, &mut cache)?);
+                    expansions += 1;
+                } else {
+                    search = Search::Truncated;
+                }
+            }
+            search
+        } else {
+            let width = count * ENUMERATE_BEAM;
+            let mut beam: Vec<Candidate> = vec![start];
+            for _ in 0..ENUMERATE_MAX_LENGTH {
+                let mut next: Vec<Candidate> = Vec::new();
+                for candidate in &beam {
+                    let (ended, partial): (Vec<Candidate>, Vec<Candidate>) = self
+                        .extend(candidate, &mut cache)?
+                        .into_iter()
+                        .partition(|candidate| candidate.ended);
+                    words.extend(ended);
+                    next.extend(partial);
+                }
+
+                next.sort_by(|a, b| b.cmp(a));
+                next.truncate(width);
+                beam = next;
+                words.sort_by(|a, b| b.cmp(a));
+                words.truncate(count);
+
+                // Once the full list is found, no partial word less probable than the last of it can catch up.
+                let settled = words.len() == count && beam.first().is_none_or(|best| words.last().is_some_and(|last| best <= last));
+                if beam.is_empty() || settled {
+                    break;
+                }
+            }
+            Search::Beam
+        };
+
+        Ok((
+            words
+                .iter()
+                .map(|word| {
+                    let text: String = word.tokens.iter().map(|&token| self.vocab.token(token)).collect();
+                    (self.present(&text), word.log_probability.exp())
+                })
+                .collect(),
+            search,
+        ))
+    }
+
+    // The geometric mean of the per-character probabilities of a word, so words of different lengths compare.
+    fn likelihood(&self, word: &str) -> Result<f32, VibeError> {
+        let steps = self.score(word)?;
+        let log_probability: f32 = steps.iter().map(|step| step.probability.ln()).sum();
+
+        Ok((log_probability / steps.len() as f32).exp())
+    }
+
+    // Score each character of a word (and its end) by the probability the model gives it, along with the
+    // context window the prediction was conditioned on.
+    pub fn score(&self, word: &str) -> Result<Vec<ScoreStep>, VibeError> {
+        let mut letters: Vec<u32> = self.vocab.encode(&word.trim().to_lowercase())?;
+        letters.push(self.hyperparameters.end);
+
+        // Slide the context window over the word the same way tokenize does.
+        //
+        // NOTE: without padding the opening tokens are still scored from delimiters, which the model never
+        // trained on, so their probabilities don't mean much.
+        let mut contexts: Vec<Vec<u32>> = Vec::new();
+        let mut context: Vec<u32> = vec![0; self.hyperparameters.block_size];
+        for (position, &letter) in letters.iter().enumerate() {
+            contexts.push(context.clone());
+            tokenize::advance(&mut context, letter, position, self.padding);
+        }
+
+        let input = Tensor::from_vec(
+            contexts.iter().flatten().copied().collect::<Vec<u32>>(),
+            (contexts.len(), self.hyperparameters.block_size),
+            &self.device,
+        )?;
+        let probs = ops::softmax(&self.logits(&input)?, 1)?.to_vec2::<f32>()?;
+
+        Ok(contexts
+            .iter()
+            .zip(letters)
+            .zip(probs)
+            .map(|((context, letter), probs)| ScoreStep {
+                context: self.vocab.display(context),
+                token: self.vocab.token(letter),
+                probability: probs[letter as usize],
+            })
+            .collect())
+    }
+
+    pub fn load(&mut self) -> Result<(), VibeError> {
+        if Path::new(&self.model_file).exists() {
+            self.load_from(&self.model_file.clone())?;
+        }
+
+        Ok(())
+    }
+
+    // Load the newest autosave, returning the iteration it was saved at. Autosaves are only looked for next to
+    // the configured model file, and one saved with different sizes is refused like any other load.
+    pub fn resume(&mut self) -> Result<Option<usize>, VibeError> {
+        let newest = (0..AUTOSAVE_SLOTS)
+            .filter_map(|slot| {
+                let path = self.autosave_path(slot);
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((modified, slot, path))
+            })
+            .max_by_key(|(modified, _, _)| *modified);
+        let Some((_, slot, path)) = newest else {
+            return Ok(None);
+        };
+
+        let iteration = self
+            .load_from(&path)?
+            .ok_or_else(|| VibeError::new(format!("{} doesn't record the iteration it was saved at", path)))?;
+        // Carry on rotating after the resumed slot so it's the last to be overwritten.
+        self.autosave_count = slot + 1;
+
+        Ok(Some(iteration))
+    }
+
+    // Load the parameters from a file, along with the iteration and training seed it was saved at if it's an
+    // autosave.
+    fn load_from(&mut self, path: &String) -> Result<Option<usize>, VibeError> {
+        let model = safetensors::load(path, &self.device)?;
+
+        let mut parameters = self.snapshot()?;
+        if let Some(parameter) = model.get("c") {
+            parameters.c = parameter.clone();
+        }
+        if let Some(parameter) = model.get("weights_1") {
+            parameters.weights_1 = parameter.clone();
+        }
+        if let Some(parameter) = model.get("biases_1") {
+            parameters.biases_1 = parameter.clone();
+        }
+        if let Some(parameter) = model.get("weights_2") {
+            parameters.weights_2 = parameter.clone();
+        }
+        if let Some(parameter) = model.get("biases_2") {
+            parameters.biases_2 = parameter.clone();
+        }
+
+        // Only take the loaded parameters if they were saved with the same sizes and settings. The model keeps
+        // the vocabulary it was trained with, data that changed since would otherwise reorder its tokens.
+        let vocab = self
+            .check_parameters(&parameters)
+            .and_then(|_| check_setting(&model, "padding", self.padding.name()))
+            .and_then(|_| check_setting(&model, "reverse", &self.reverse.to_string()))
+            .and_then(|_| match model.get("vocab") {
+                Some(tensor) => self.vocab.with_tokens(tensor_text(tensor)?.split('\n').map(String::from).collect()),
+                None => Ok(self.vocab.clone()),
+            })
+            .map_err(|err| VibeError::new(format!("unable to load {}: {}", path, err)))?;
+        self.start_bias = start_bias(&self.start_weights, &vocab, &self.device)?;
+        self.vocab = vocab;
+        self.restore(&parameters)?;
+        self.fresh = false;
+        if let Some(seed) = model.get("train_seed") {
+            self.train_seed = seed.to_vec1::<i64>()?[0] as u64;
+        }
+
+        match model.get("iteration") {
+            Some(iteration) => Ok(Some(iteration.to_vec1::<u32>()?[0] as usize)),
+            None => Ok(None),
+        }
+    }
+
+    // Make sure the parameter shapes match the configured sizes.
+    fn check_parameters(&self, parameters: &Parameters) -> Result<(), VibeError> {
+        let hyperparameters = &self.hyperparameters;
+        let (vocab_size, hidden_size) = (hyperparameters.vocab_size, hyperparameters.hidden_size);
+
+        check_shape(
+            "c",
+            &parameters.c,
+            &[vocab_size, hyperparameters.embedding_size],
+            "vocab and embedding_size",
+        )?;
+        check_shape(
+            "weights_1",
+            &parameters.weights_1,
+            &[hyperparameters.input_size(), hidden_size],
+            "embedding_size * block_size and hidden_size",
+        )?;
+        check_shape("biases_1", &parameters.biases_1, &[hidden_size], "hidden_size")?;
+        check_shape(
+            "weights_2",
+            &parameters.weights_2,
+            &[hidden_size, vocab_size],
+            "hidden_size and vocab",
+        )?;
+        check_shape("biases_2", &parameters.biases_2, &[vocab_size], "vocab")?;
+
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<(), VibeError> {
+        self.save_to(&self.model_file, None)
+    }
+
+    // Save the parameters with the settings they only make sense under, autosaves also record the iteration they
+    // were saved at and the training seed so they can be resumed.
+    fn save_to(&self, path: &String, iteration: Option<usize>) -> Result<(), VibeError> {
+        let mut tensors: HashMap<&str, Tensor> = HashMap::new();
+        tensors.insert("c", self.c.as_tensor().clone());
+        tensors.insert("weights_1", self.weights_1.as_tensor().clone());
+        tensors.insert("biases_1", self.biases_1.as_tensor().clone());
+        tensors.insert("weights_2", self.weights_2.as_tensor().clone());
+        tensors.insert("biases_2", self.biases_2.as_tensor().clone());
+        tensors.insert("padding", text_tensor(self.padding.name())?);
+        tensors.insert("reverse", text_tensor(&self.reverse.to_string())?);
+        tensors.insert("vocab", text_tensor(&self.vocab.tokens().join("\n"))?);
+        if let Some(iteration) = iteration {
+            tensors.insert("iteration", Tensor::new(&[iteration as u32], &Device::Cpu)?);
+            tensors.insert("train_seed", Tensor::new(&[self.train_seed as i64], &Device::Cpu)?);
+        }
+
+        safetensors::save(&tensors, path)?;
+
+        Ok(())
+    }
+
+    // Render the hyperparameters, vocabulary and every parameter as nested JSON arrays with their shapes.
+    pub fn to_json(&self) -> Result<String, VibeError> {
+        let hyperparameters = &self.hyperparameters;
+        let vocab: Vec<String> = (0..self.vocab.size() as u32)
+            .map(|index| output::json_string(&self.vocab.token(index)))
+            .collect();
+
+        let mut parameters: Vec<String> = Vec::new();
+        for (name, param) in [
+            ("c", &self.c),
+            ("weights_1", &self.weights_1),
+            ("biases_1", &self.biases_1),
+            ("weights_2", &self.weights_2),
+            ("biases_2", &self.biases_2),
+        ] {
+            let values = param.as_tensor().flatten_all()?.to_vec1::<f32>()?;
+            parameters.push(format!(
+                "    \"{}\": {{\"shape\": {:?}, \"data\": {}}}",
+                name,
+                param.dims(),
+                json_array(&values, param.dims())
+            ));
+        }
+
+        Ok(format!(
+            "{{\n  \"hyperparameters\": {{\"tokenizer\": \"{}\", \"vocab_size\": {}, \"end\": {}, \"block_size\": {}, \"embedding_size\": {}, \"hidden_size\": {}, \"prev_onehot\": {}}},\n  \"vocab\": [{}],\n  \"parameters\": {{\n{}\n  }}\n}}\n",
+            self.vocab.tokenizer().name(),
+            hyperparameters.vocab_size,
+            hyperparameters.end,
+            hyperparameters.block_size,
+            hyperparameters.embedding_size,
+            hyperparameters.hidden_size,
+            hyperparameters.prev_onehot,
+            vocab.join(", "),
+            parameters.join(",\n")
+        ))
+    }
+
+    // The path of an autosave slot, next to the model file.
+    fn autosave_path(&self, slot: usize) -> String {
+        match self.model_file.strip_suffix(".safetensors") {
+            Some(stem) => format!("{}.autosave{}.safetensors", stem, slot),
+            None => format!("{}.autosave{}", self.model_file, slot),
+        }
+    }
+
+    // Save to the next autosave slot. A failed autosave is only reported, it shouldn't stop training.
+    fn autosave(&mut self, iteration: usize, sender: &Sender<AppMessage>) {
+        let path = self.autosave_path(self.autosave_count % AUTOSAVE_SLOTS);
+        self.autosave_count += 1;
+
+        let text = match self.save_to(&path, Some(iteration)) {
+            Ok(()) => format!("autosaved iteration {} to {}", iteration, path),
+            Err(err) => format!("warning: autosave to {} failed: {}", path, err),
+        };
+        let _ = sender.send(AppMessage::Model(ModelResultMessage::Status { run: self.run, text }));
+    }
+
+    // Training rounds.
+    //
+    // NOTE: the data is randomly batched every training round and all weights adjusted based on
+    // the batch loss. This speeds up training by not having to calculate the entire gradient every
+    // round. In the tradeoff between calculating the exact gradient every round versus running
+    // more rounds, running more rounds shows better results.
+    //
+    // Training stops early once past the deadline, if there is one.
+    pub fn train(
+        &mut self,
+        iterations: usize,
+        start: usize,
+        deadline: Option<Instant>,
+        sender: &Sender<AppMessage>,
+    ) -> Result<(), VibeError> {
+        // Nothing to train, but the app still waits to hear training is over.
+        if iterations == 0 {
+            sender.send(AppMessage::Model(ModelResultMessage::Finished))?;
+            return Ok(());
+        }
+
+        // Retry loading data that failed to load at startup.
+        if self.training_data.is_none() {
+            self.load_data()?;
+        }
+        self.fresh = false;
+
+        self.train_iterations(iterations, start, deadline, sender)?;
+
+        sender.send(AppMessage::Model(ModelResultMessage::Finished))?;
+
+        Ok(())
+    }
+
+    // Train on whatever data is loaded, reporting progress as it goes.
+    fn train_iterations(
+        &mut self,
+        iterations: usize,
+        start: usize,
+        deadline: Option<Instant>,
+        sender: &Sender<AppMessage>,
+    ) -> Result<(), VibeError> {
+        if self.sample_checkpoints {
+            self.send_sample("before training", sender)?;
+        }
+        sender.send(AppMessage::Model(ModelResultMessage::BatchSize {
+            run: self.run,
+            size: self.hyperparameters.batch_size,
+        }))?;
+
+        // Training losses are averaged over each progress interval to keep from flooding the UI.
+        let mut pending_loss: f32 = 0.;
+        let mut pending_count: usize = 0;
+
+        // With early stopping, the best weights are kept so they can be restored when training stops.
+        let mut best: Option<(f32, Parameters)> = None;
+        let mut stale_checks: usize = 0;
+
+        // Notes about the run reported once training finishes.
+        let mut status: Vec<String> = Vec::new();
+
+        // With hard mining, batches favor the examples with the highest loss as of the last refresh. Balanced
+        // starts only depend on the data so their weights are set once.
+        let mut weighted_examples: Option<WeightedIndex<f32>> = None;
+        let mut rng = rand::rng();
+        let examples = self.data()?.input.dims()[0];
+        let loss_weights = self.loss_weights()?;
+
+        // Validation, evaluation and hard mining refreshes happen ten times a run, or every iteration of a run
+        // shorter than ten.
+        let check_interval = (iterations / 10).max(1);
+
+        for count in start..start + iterations {
+            if self.stop_training.load(Ordering::Relaxed) {
+                break;
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                status.push(format!(
+                    "stopped at the time limit after {} of {} iterations",
+                    count - start,
+                    iterations
+                ));
+                break;
+            }
+
+            if (self.hard_mining || self.balance_starts)
+                && (weighted_examples.is_none() || (self.hard_mining && count % check_interval == 0))
+            {
+                weighted_examples = Some(self.example_weights()?);
+            }
+
+            // Each iteration's batch comes from its own generator, so resuming at an iteration draws the same
+            // batches without replaying the ones before it.
+            let mut batch_rng = StdRng::seed_from_u64(self.train_seed.wrapping_add(count as u64));
+            let batch_indices = Tensor::from_vec(
+                (0..self.hyperparameters.batch_size)
+                    .map(|_| match &weighted_examples {
+                        Some(weights) => weights.sample(&mut batch_rng) as u32,
+                        None => batch_rng.random_range(0..examples) as u32,
+                    })
+                    .collect::<Vec<u32>>(),
+                self.hyperparameters.batch_size,
+                &self.device,
+            )?;
+
+            let input = self.data()?.input.index_select(&batch_indices.flatten_all()?, 0)?;
+            let target = self.data()?.target.index_select(&batch_indices.flatten_all()?, 0)?;
+            let batch_weights = match &loss_weights {
+                Some(weights) => Some(weights.index_select(&batch_indices, 0)?),
+                None => None,
+            };
+            let loss = self.training_loss(&input, &target, batch_weights.as_ref())?;
+
+            // Measured on the batch before the update, the same activations the loss was computed from.
+            if self.debug_saturation > 0 && count % self.debug_saturation == 0 {
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::Saturation {
+                    run: self.run,
+                    iteration: count,
+                    fraction: self.saturation(&input)?,
+                }));
+            }
+
+            let histograms = self.debug_histograms > 0 && count % self.debug_histograms == 0;
+            let gradients = self.backpropagate(&loss, histograms)?;
+            self.update_ema()?;
+            if histograms {
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::Histograms {
+                    run: self.run,
+                    iteration: count,
+                    weights: self.weight_histograms()?,
+                    gradients,
+                }));
+            }
+
+            if self.autosave_every > 0 && (count + 1) % self.autosave_every == 0 {
+                self.autosave(count + 1, sender);
+            }
+
+            // A single word is only a handful of tiny forward passes, next to nothing beside a training batch.
+            if self.sample_every > 0 && (count + 1) % self.sample_every == 0 {
+                let (text, _) = self.sample_prefixed(&mut rng, None)?;
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::LiveSample {
+                    run: self.run,
+                    iteration: count + 1,
+                    text: self.present(&text),
+                }));
+            }
+
+            // Send progress updates.
+            let loss_val: f32 = loss.clone().to_device(&Device::Cpu)?.to_scalar::<f32>()? * self.loss_scale;
+            if let Some(loss_log) = &mut self.loss_log {
+                loss_log.write(&LossType::Training, count, loss_val)?;
+            }
+            if let Some(metrics) = &mut self.metrics {
+                metrics.record_training(count, loss_val, self.hyperparameters.learn_rate, count + 1 == start + iterations)?;
+            }
+
+            pending_loss += loss_val;
+            pending_count += 1;
+            if pending_count == self.progress_interval || count + 1 == start + iterations {
+                let _ = sender.send(AppMessage::Model(ModelResultMessage::Progress {
+                    run: self.run,
+                    loss_type: LossType::Training,
+                    iteration: count,
+                    loss: pending_loss / pending_count as f32,
+                }));
+                pending_loss = 0.;
+                pending_count = 0;
+            }
+
+            // Send evaluation and validation progress every few iterations.
+            if count % check_interval == 0 {
+                if let Some(evaluation_loss_val) = self.evaluation_loss()? {
+                    if let Some(loss_log) = &mut self.loss_log {
+                        loss_log.write(&LossType::Evaluation, count, evaluation_loss_val)?;
+                    }
+                    let _ = sender.send(AppMessage::Model(ModelResultMessage::Progress {
+                        run: self.run,
+                        loss_type: LossType::Evaluation,
+                        iteration: count,
+                        loss: evaluation_loss_val,
+                    }));
+                }
+
+                if !self.validation || count < self.val_warmup {
+                    continue;
+                }
+
+                let validation_loss = self.forward_pass(&self.data()?.validation_input, &self.data()?.validation_target)?;
+                let validation_loss_val = validation_loss.to_vec0::<f32>()? * self.loss_scale;
+                if let Some(loss_log) = &mut self.loss_log {
+                    loss_log.write(&LossType::Validation, count, validation_loss_val)?;
+                }
+                if let Some(metrics) = &mut self.metrics {
+                    metrics.record_validation(validation_loss_val);
+                }
+
+                sender.send(AppMessage::Model(ModelResultMessage::Progress {
+                    run: self.run,
+                    loss_type: LossType::Validation,
+                    iteration: count,
+                    loss: validation_loss_val,
+                }))?;
+
+                if self.patience > 0 {
+                    match &best {
+                        Some((best_loss, _)) if validation_loss_val >= *best_loss => stale_checks += 1,
+                        _ => {
+                            best = Some((validation_loss_val, self.snapshot()?));
+                            stale_checks = 0;
+                        }
+                    }
+
+                    if stale_checks >= self.patience
+                        && let Some((best_loss, parameters)) = &best
+                    {
+                        self.restore(parameters)?;
+                        status.push(format!(
+                            "early stopping at iteration {}, restored the best weights (validation loss {:.4})",
+                            count, best_loss
+                        ));
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(loss_log) = &mut self.loss_log {
+            loss_log.flush()?;
+        }
+
+        // Perplexity is the exponent of the loss in nats, whatever units it's reported in.
+        if let (Some(path), Some(evaluation_loss_val)) = (&self.evaluation_file, self.evaluation_loss()?) {
+            status.push(format!(
+                "{} loss {:.4}, perplexity {:.2}",
+                path,
+                evaluation_loss_val,
+                (evaluation_loss_val / self.loss_scale).exp()
+            ));
+        }
+        if let Some((letter, share)) = self.collapsed_letter()? {
+            status.push(format!(
+                "{:.0}% of generated characters are '{}', the data may not match the vocabulary, check it with --print-vocab",
+                share * 100.,
+                letter
+            ));
+        }
+        if !status.is_empty() {
+            let _ = sender.send(AppMessage::Model(ModelResultMessage::Status {
+                run: self.run,
+                text: status.join(", "),
+            }));
+        }
+
+        if self.sample_checkpoints {
+            self.send_sample("after training", sender)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Names of the parameters in the order they're stored, for reporting on them.
+const PARAMETER_NAMES: [&str; 5] = ["embeddings", "hidden weights", "hidden biases", "output weights", "output biases"];
+
+// Bucket the values of a tensor evenly from its min to its max.
+fn histogram(name: &str, tensor: &Tensor) -> Result<Histogram, VibeError> {
+    let values = tensor.flatten_all()?.to_device(&Device::Cpu)?.to_vec1::<f32>()?;
+    let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+
+    let width = (max - min) / HISTOGRAM_BUCKETS as f32;
+    let mut counts = vec![0; HISTOGRAM_BUCKETS];
+    for value in values {
+        let bucket = if width > 0. { ((value - min) / width) as usize } else { 0 };
+        counts[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+
+    Ok(Histogram {
+        name: name.to_string(),
+        min,
+        max,
+        counts,
+    })
+}
+
+// Parse start weights given as `token=weight` pairs separated by commas, e.g. `a=2,e=2,x=0`.
+pub fn parse_start_weights(spec: &str) -> Result<Vec<(String, f32)>, VibeError> {
+    spec.split(',')
+        .map(|pair| {
+            let (token, weight) = pair
+                .split_once('=')
+                .ok_or_else(|| VibeError::new(format!("invalid start weight {}, expected token=weight", pair)))?;
+            let weight = str::parse::<f32>(weight.trim())?;
+            if !weight.is_finite() || weight < 0. {
+                return Err(VibeError::new(format!("invalid start weight {}, weights can't be negative", pair)));
+            }
+
+            Ok((token.trim().to_lowercase(), weight))
+        })
+        .collect()
+}
+
+// Turn start weights into a logit bias over the vocabulary. Scaling a token's probability by its weight is the
+// same as adding the log of the weight to its logit, so a weight of 0 rules the token out.
+fn start_bias(weights: &[(String, f32)], vocab: &Vocab, device: &Device) -> Result<Option<Tensor>, VibeError> {
+    if weights.is_empty() {
+        return Ok(None);
+    }
+
+    let mut bias = vec![0f32; vocab.size()];
+    for (token, weight) in weights {
+        match vocab.encode(token)?.as_slice() {
+            [index] => bias[*index as usize] = weight.ln(),
+            _ => {
+                return Err(VibeError::new(format!(
+                    "start weight token {} isn't a single token of the vocabulary",
+                    token
+                )));
+            }
+        }
+    }
+
+    Ok(Some(Tensor::from_vec(bias, (1, vocab.size()), device)?))
+}
+
+// Compile the generated word filter.
+pub fn parse_filter(pattern: &str) -> Result<Regex, VibeError> {
+    Regex::new(pattern).map_err(|e| VibeError::new(format!("invalid filter regex {}: {}", pattern, e)))
+}
+
+// Read the banned substrings, one per line with `#` comments. They're lowercased and normalized to the
+// vocabulary's letters the same way the training words are so they match case-insensitively.
+pub fn read_ban_list(path: &str) -> Result<Vec<String>, VibeError> {
+    let content = fs::read_to_string(path).map_err(|e| VibeError::new(format!("unable to open ban list {}: {}", path, e)))?;
+
+    Ok(content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.to_lowercase()
+                .chars()
+                .map(|letter| convert::itol(convert::ltoi(letter)))
+                .collect()
+        })
+        .collect())
+}
+
+// Nest a flat row-major list of values into JSON arrays following the shape.
+fn json_array(values: &[f32], shape: &[usize]) -> String {
+    match shape {
+        [] | [_] => {
+            // JSON has no representation for infinities or NaN.
+            let values: Vec<String> = values
+                .iter()
+                .map(|value| if value.is_finite() { value.to_string() } else { "null".to_string() })
+                .collect();
+            format!("[{}]", values.join(", "))
+        }
+        [_, inner @ ..] => {
+            let stride: usize = inner.iter().product();
+            let rows: Vec<String> = values.chunks(stride.max(1)).map(|row| json_array(row, inner)).collect();
+            format!("[{}]", rows.join(", "))
+        }
+    }
+}
+
+// Load the saved model and write it out as JSON.
+pub fn export_json(options: &Options, path: &str) -> Result<(), VibeError> {
+    if !Path::new(&options.model_file).exists() {
+        return Err(VibeError::new(format!("no saved model at {} to export", options.model_file)));
+    }
+
+    let mut model = Model::init(options)?;
+    model.load()?;
+
+    fs::write(path, model.to_json()?).map_err(|e| VibeError::new(format!("unable to write {}: {}", path, e)))?;
+    println!("exported {} to {}", options.model_file, path);
+
+    Ok(())
+}
+
+// Print the most probable complete words of the saved model, from the prefix when there is one.
+pub fn enumerate_top(options: &Options) -> Result<(), VibeError> {
+    if !Path::new(&options.model_file).exists() {
+        return Err(VibeError::new(format!("no saved model at {} to enumerate", options.model_file)));
+    }
+
+    let mut model = Model::init(options)?;
+    model.load()?;
+
+    let count = options.enumerate_top;
+    if model.hyperparameters.block_size > ENUMERATE_BLOCK_SIZE {
+        println!(
+            "warning: block size {} is too large to enumerate exactly, using a beam search of width {} instead",
+            model.hyperparameters.block_size,
+            count * ENUMERATE_BEAM
+        );
+    }
+
+    let (words, search) = model.enumerate(count)?;
+    let method = match search {
+        Search::Exact => "exact".to_string(),
+        Search::Truncated => format!(
+            "not exact, the search stopped at {} expansions or {} tokens a word",
+            ENUMERATE_EXPANSIONS, ENUMERATE_MAX_LENGTH
+        ),
+        Search::Beam => "beam search".to_string(),
+    };
+    println!("top {} words of {} ({})", words.len(), options.model_file, method);
+    println!("{:>5} {:>16} {:>12}", "rank", "word", "probability");
+    for (rank, (word, probability)) in words.iter().enumerate() {
+        println!("{:>5} {:>16} {:>12.6}", rank + 1, word, probability);
+    }
+
+    Ok(())
+}
+
+// Main event loop for the model thread.
+pub fn run_model(
+    commands: Receiver<ModelCommandMessage>,
+    results: Sender<AppMessage>,
+    options: &Options,
+    cancel: Arc<AtomicBool>,
+    stop_training: Arc<AtomicBool>,
+    run: usize,
+) -> Result<(), VibeError> {
+    let mut model = match Model::init(options) {
+        Ok(model) => Model {
+            cancel,
+            stop_training,
+            run,
+            ..model
+        },
+        // The app waits on every model to be ready, so one that failed to build still says so.
+        Err(err) => {
+            _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err: err.clone() }));
+            _ = results.send(AppMessage::Model(ModelResultMessage::Ready { run, built: false }));
+            return Err(err);
+        }
+    };
+
+    // Resuming falls back to the usual start when there's no autosave to resume from, or it can't be used.
+    let (resumed, resume_note) = match options.resume.then(|| model.resume()) {
+        Some(Ok(Some(iteration))) => (
+            Some(iteration),
+            Some(format!("resumed from the autosave at iteration {}", iteration)),
+        ),
+        Some(Ok(None)) => (None, Some("warning: no autosave to resume from, starting fresh".to_string())),
+        Some(Err(err)) => (None, Some(format!("warning: unable to resume, starting fresh: {}", err))),
+        None => (None, None),
+    };
+
+    // A model saved with different sizes can't be used, report it and carry on with fresh weights.
+    if resumed.is_none()
+        && let Err(err) = model.load()
+    {
+        _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+    }
+
+    // Warnings about the options and how resuming went lead the data summary.
+    let mut notes: Vec<String> = options.warnings.iter().map(|warning| format!("warning: {}", warning)).collect();
+    notes.extend(resume_note);
+
+    // A bad data file shouldn't stop the app, report it and wait for a reload.
+    match model.load_data().and_then(|_| model.data_summary()) {
+        Ok(text) => {
+            notes.push(text);
+            _ = results.send(AppMessage::Model(ModelResultMessage::Status {
+                run,
+                text: notes.join(", "),
+            }));
+        }
+        Err(err) => {
+            if !notes.is_empty() {
+                _ = results.send(AppMessage::Model(ModelResultMessage::Status {
+                    run,
+                    text: notes.join(", "),
+                }));
+            }
+            _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+        }
+    }
+    if let Some(iteration) = resumed {
+        _ = results.send(AppMessage::Model(ModelResultMessage::Resumed { run, iteration }));
+    }
+    _ = results.send(AppMessage::Model(ModelResultMessage::Ready { run, built: true }));
+
+    loop {
+        match commands.recv() {
+            Ok(ModelCommandMessage::Train {
+                iterations,
+                start,
+                pretrain,
+            }) => {
+                let deadline = model.deadline();
+                model
+                    .pretrain(pretrain, start, deadline, &results)
+                    .and_then(|_| model.train(iterations, start + pretrain, deadline, &results))
+                    .unwrap_or_else(|err| {
+                        _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err: err }));
+                    });
+            }
+
+            Ok(ModelCommandMessage::Vibe { count }) => {
+                model.generate(count, &results).unwrap_or_else(|err| {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err: err }));
+                });
+            }
+
+            Ok(ModelCommandMessage::ReloadData) => match model.load_data().and_then(|_| model.data_summary()) {
+                Ok(text) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Status { run, text }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+                }
+            },
+
+            Ok(ModelCommandMessage::Describe) => {
+                _ = results.send(AppMessage::Model(ModelResultMessage::Description {
+                    text: model.describe(),
+                    layers: model.layers(),
+                }));
+            }
+
+            Ok(ModelCommandMessage::Probe { context }) => match model.next_token_distribution(&model.orient(&context)) {
+                Ok(distribution) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Probed { context, distribution }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+                }
+            },
+
+            Ok(ModelCommandMessage::LossFloor) => match model.loss_floor() {
+                Ok(loss) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::LossFloor { loss }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+                }
+            },
+
+            Ok(ModelCommandMessage::PositionLosses) => match model.position_losses() {
+                Ok(losses) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::PositionLosses { losses }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+                }
+            },
+
+            Ok(ModelCommandMessage::Score { word }) => match model.score(&model.orient(&word)) {
+                Ok(steps) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Scored { word, steps }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error { run, err }));
+                }
+            },
+
+            Ok(ModelCommandMessage::SwitchDevice { name }) => match model.switch_device(&name) {
+                Ok(()) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::DeviceSwitched { run, name }));
+                }
+                Err(err) => {
+                    _ = results.send(AppMessage::Model(ModelResultMessage::Error {
+                        run,
+                        err: VibeError::new(format!("unable to switch to {}, staying on the current device: {}", name, err)),
+                    }));
+                }
+            },
+
+            Ok(ModelCommandMessage::Save) => {
+                model.save()?;
+            }
+
+            Ok(ModelCommandMessage::Shutdown) => {
+                break;
+            }
+
+            Err(_) => {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::message;
+
+    // A small model on the cpu that never touches a saved model file.
+    fn test_options() -> Options {
+        let mut options = Options::new();
+        options.device = "cpu".to_string();
+        options.hidden_size = 16;
+        options.embedding_size = 4;
+        options.batch_size = 32;
+        options.model_file = "/nonexistent/model.safetensors".to_string();
+        options
+    }
+
+    #[test]
+    fn train_zero_iterations_only_finishes() {
+        let mut model = Model::init(&test_options()).unwrap();
+        let (sender, receiver) = message::create_data_channel();
+
+        model.train(0, 0, None, &sender).unwrap();
+        drop(sender);
+
+        let messages: Vec<AppMessage> = receiver.iter().collect();
+        assert_eq!(messages.len(), 1);
+        assert!(matches!(messages[0], AppMessage::Model(ModelResultMessage::Finished)));
+    }
+
+    fn mean_and_std(var: &Var) -> (f32, f32) {
+        let values = var.as_tensor().flatten_all().unwrap().to_vec1::<f32>().unwrap();
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f32>() / values.len() as f32;
+
+        (mean, variance.sqrt())
+    }
+
+    // Uniform draws from [0, scale) have a mean of scale / 2 and a std of scale / sqrt(12).
+    #[test]
+    fn uniform_init_matches_its_distribution() {
+        let var = random_parameter(InitDistribution::Uniform, 0.5, (200, 500), &Device::Cpu).unwrap();
+        let (mean, std) = mean_and_std(&var);
+        assert!((mean - 0.25).abs() < 0.005, "mean {}", mean);
+        assert!((std - 0.5 / 12f32.sqrt()).abs() < 0.005, "std {}", std);
+    }
+
+    #[test]
+    fn normal_init_matches_its_distribution() {
+        let var = random_parameter(InitDistribution::Normal, 0.5, (200, 500), &Device::Cpu).unwrap();
+        let (mean, std) = mean_and_std(&var);
+        assert!(mean.abs() < 0.005, "mean {}", mean);
+        assert!((std - 0.5).abs() < 0.005, "std {}", std);
+    }
+
+    // A handful of words with enough examples between them for a batch of 32.
+    fn words() -> Vec<String> {
+        ["anna", "bob", "carol", "dave", "eve", "frank", "grace", "heidi", "ivan", "judy"]
+            .iter()
+            .map(|word| word.to_string())
+            .collect()
+    }
+
+    fn values(tensor: &Tensor) -> Vec<f32> {
+        tensor.flatten_all().unwrap().to_vec1::<f32>().unwrap()
+    }
+
+    fn assert_values_close(actual: &Tensor, expected: &Tensor) {
+        for (actual, expected) in values(actual).iter().zip(values(expected)) {
+            assert!((actual - expected).abs() < 1e-6, "{} vs {}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn frozen_layers_are_unchanged_by_a_step() {
+        let mut options = test_options();
+        options.no_validation = true;
+        options.freeze = FrozenLayers::from_names("embeddings,hidden").unwrap();
+        let mut model = Model::init(&options).unwrap();
+        model.load_words(words()).unwrap();
+        let before = model.snapshot().unwrap();
+
+        let (sender, _receiver) = message::create_data_channel();
+        model.train(1, 0, None, &sender).unwrap();
+        let after = model.snapshot().unwrap();
+
+        assert_eq!(values(&before.c), values(&after.c));
+        assert_eq!(values(&before.weights_1), values(&after.weights_1));
+        assert_eq!(values(&before.biases_1), values(&after.biases_1));
+        assert_ne!(values(&before.weights_2), values(&after.weights_2));
+        assert_ne!(values(&before.biases_2), values(&after.biases_2));
+    }
+
+    // The average starts from the first step's parameters, then keeps `decay` of itself every step after.
+    #[test]
+    fn ema_tracks_the_parameters() {
+        let mut options = test_options();
+        options.no_validation = true;
+        options.ema_decay = 0.75;
+        let mut model = Model::init(&options).unwrap();
+        model.load_words(words()).unwrap();
+        assert!(model.ema.is_none());
+
+        let (sender, _receiver) = message::create_data_channel();
+        model.train(1, 0, None, &sender).unwrap();
+        let first = model.snapshot().unwrap();
+        let ema = model.ema.as_ref().unwrap();
+        assert_values_close(&ema.c, &first.c);
+        assert_values_close(&ema.weights_2, &first.weights_2);
+
+        model.train(1, 1, None, &sender).unwrap();
+        let second = model.snapshot().unwrap();
+        let ema = model.ema.as_ref().unwrap();
+        for (ema, first, second) in [
+            (&ema.c, &first.c, &second.c),
+            (&ema.weights_1, &first.weights_1, &second.weights_1),
+            (&ema.biases_1, &first.biases_1, &second.biases_1),
+            (&ema.weights_2, &first.weights_2, &second.weights_2),
+            (&ema.biases_2, &first.biases_2, &second.biases_2),
+        ] {
+            let expected = ((first * 0.75).unwrap() + (second * 0.25).unwrap()).unwrap();
+            assert_values_close(ema, &expected);
+        }
+    }
+
+    // Each row is the block's embeddings followed by the one-hot of its last token, which is what weights_1 takes.
+    #[test]
+    fn prev_onehot_is_appended_to_the_embeddings() {
+        let mut options = test_options();
+        options.prev_onehot = true;
+        let model = Model::init(&options).unwrap();
+
+        let contexts = Tensor::new(&[[0u32, 1, 2], [3, 4, 5]], &Device::Cpu).unwrap();
+        let embedded = model.embed(&contexts).unwrap();
+        assert_eq!(embedded.dims(), &[2, 4 * 3 + 27]);
+        assert_eq!(model.weights_1.dims(), &[4 * 3 + 27, 16]);
+
+        let onehot = embedded.narrow(1, 4 * 3, 27).unwrap().to_vec2::<f32>().unwrap();
+        for (row, last) in onehot.iter().zip([2, 5]) {
+            let expected: Vec<f32> = (0..27).map(|index| if index == last { 1. } else { 0. }).collect();
+            assert_eq!(row, &expected);
+        }
+
+        let embeddings = embedded.narrow(1, 0, 4 * 3).unwrap();
+        let expected = model
+            .c
+            .as_tensor()
+            .index_select(&contexts.flatten_all().unwrap(), 0)
+            .unwrap()
+            .reshape((2, 12))
+            .unwrap();
+        assert_eq!(embeddings.to_vec2::<f32>().unwrap(), expected.to_vec2::<f32>().unwrap());
+        assert_eq!(model.logits(&contexts).unwrap().dims(), &[2, 27]);
+    }
+
+    // The words a model generates, in order.
+    fn generated(model: &mut Model, count: usize) -> Vec<String> {
+        let (sender, receiver) = message::create_data_channel();
+        model.generate(count, &sender).unwrap();
+        drop(sender);
+
+        receiver
+            .iter()
+            .filter_map(|message| match message {
+                AppMessage::Model(ModelResultMessage::Generated { text, .. }) => Some(text),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn generating_model(seed: Option<u64>) -> Model {
+        let mut options = test_options();
+        options.generate_seed = seed;
+        options.target_length = 4;
+
+        let mut model = Model::init(&options).unwrap();
+        model.load_words(words()).unwrap();
+        model
+    }
+
+    // Every word draws from its own seeded generator in the same order either way, so a pinned seed gives the
+    // same list whether the words are sampled one at a time or together.
+    #[test]
+    fn parallel_generation_matches_sequential_with_a_seed() {
+        let mut model = generating_model(Some(11));
+        let sequential = generated(&mut model, 40);
+        model.generate_parallel = 8;
+        let parallel = generated(&mut model, 40);
+
+        assert_eq!(sequential.len(), 40);
+        assert_eq!(sequential, parallel);
+    }
+
+    // Without a seed the lists differ, but the words should still come from the same distribution.
+    #[test]
+    fn parallel_generation_matches_sequential_in_distribution() {
+        let mean_length = |words: &[String]| words.iter().map(|word| word.chars().count()).sum::<usize>() as f32 / words.len() as f32;
+
+        let mut model = generating_model(None);
+        let sequential = generated(&mut model, 1000);
+        model.generate_parallel = 16;
+        let parallel = generated(&mut model, 1000);
+
+        let (sequential, parallel) = (mean_length(&sequential), mean_length(&parallel));
+        assert!((sequential - parallel).abs() < 0.1 * sequential, "{} vs {}", sequential, parallel);
+    }
+
+    // A model trained on the single word "ab" whose output ignores the context: the logits are ln 2 for 'a' and
+    // 0 for the other 26 tokens, so the loss of every target can be worked out by hand.
+    fn fixed_batch_model(position_weights: PositionWeights, label_smoothing: f64) -> Model {
+        let mut options = test_options();
+        options.no_validation = true;
+        options.batch_size = 3;
+        options.position_weights = position_weights;
+
+        let mut model = Model::init(&options).unwrap();
+        model.load_words(vec!["ab".to_string()]).unwrap();
+        model.label_smoothing = label_smoothing;
+
+        let vocab_size = model.hyperparameters.vocab_size;
+        assert_eq!(vocab_size, 27);
+        let mut biases = vec![0f32; vocab_size];
+        biases[1] = 2f32.ln();
+        model.weights_2 = Var::zeros((options.hidden_size, vocab_size), candle_core::DType::F32, &Device::Cpu).unwrap();
+        model.biases_2 = Var::from_tensor(&Tensor::from_vec(biases, vocab_size, &Device::Cpu).unwrap()).unwrap();
+
+        model
+    }
+
+    // A model file path in the temp directory, deleted when it goes out of scope so a failed test doesn't
+    // leave it behind.
+    struct TempModelFile {
+        path: String,
+    }
+
+    impl TempModelFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("gpturd-{}-{}.safetensors", name, std::process::id()));
+            Self {
+                path: path.to_string_lossy().to_string(),
+            }
+        }
+    }
+
+    impl Drop for TempModelFile {
+        fn drop(&mut self) {
+            _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[test]
+    fn load_refuses_a_different_padding() {
+        let file = TempModelFile::new("padding");
+        let path = file.path.clone();
+        Model::init(&test_options()).unwrap().save_to(&path, None).unwrap();
+
+        let mut options = test_options();
+        options.padding = Padding::RepeatFirst;
+        let mut model = Model::init(&options).unwrap();
+        let err = model.load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("padding=delimiter"));
+
+        options.padding = Padding::Delimiter;
+        let mut model = Model::init(&options).unwrap();
+        assert!(model.load_from(&path).is_ok());
+    }
+
+    #[test]
+    fn load_refuses_a_different_direction() {
+        let file = TempModelFile::new("reverse");
+        let path = file.path.clone();
+        Model::init(&test_options()).unwrap().save_to(&path, None).unwrap();
+
+        let mut options = test_options();
+        options.suffix = "ia".to_string();
+        let mut model = Model::init(&options).unwrap();
+        let err = model.load_from(&path).unwrap_err();
+        assert!(err.to_string().contains("reverse=false"));
+
+        let mut model = Model::init(&test_options()).unwrap();
+        assert!(model.load_from(&path).is_ok());
+    }
+
+    #[test]
+    fn load_keeps_the_saved_vocabulary() {
+        let file = TempModelFile::new("vocab");
+        let path = file.path.clone();
+        let mut saved = Model::init(&test_options()).unwrap();
+        let mut tokens = saved.vocab.tokens().to_vec();
+        tokens[1..].reverse();
+        saved.vocab = saved.vocab.with_tokens(tokens.clone()).unwrap();
+        saved.save_to(&path, None).unwrap();
+
+        let mut model = Model::init(&test_options()).unwrap();
+        model.load_from(&path).unwrap();
+        assert_eq!(model.vocab.tokens(), tokens.as_slice());
+        assert_eq!(model.vocab.encode("z").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn enumeration_reports_a_truncated_search() {
+        let mut options = test_options();
+        options.block_size = 2;
+        let model = Model::init(&options).unwrap();
+
+        let (words, search) = model.enumerate(1).unwrap();
+        assert_eq!(words.len(), 1);
+        assert_eq!(search, Search::Exact);
+
+        // Far more words than the expansions allow can't all be found.
+        let (words, search) = model.enumerate(ENUMERATE_EXPANSIONS * 100).unwrap();
+        assert!(words.len() < ENUMERATE_EXPANSIONS * 100);
+        assert_eq!(search, Search::Truncated);
+    }
+
+    fn loss_of(model: &Model, weights: Option<&Tensor>) -> f32 {
+        let data = model.data().unwrap();
+        model
+            .training_loss(&data.input, &data.target, weights)
+            .unwrap()
+            .to_scalar::<f32>()
+            .unwrap()
+    }
+
+    // The targets of "ab" are 'a', 'b' and the '.' delimiter at positions 0, 1 and 2. With 28 as the softmax
+    // denominator 'a' costs ln 14 and the others ln 28.
+    #[test]
+    fn uniform_position_weights_match_forward_pass() {
+        let model = fixed_batch_model(PositionWeights::Uniform, 0.);
+        let data = model.data().unwrap();
+        assert_eq!(data.positions, vec![0, 1, 2]);
+        assert!(model.loss_weights().unwrap().is_none());
+
+        let expected = (14f32.ln() + 2. * 28f32.ln()) / 3.;
+        let forward = model.forward_pass(&data.input, &data.target).unwrap().to_scalar::<f32>().unwrap();
+        let ones = Tensor::ones(3, candle_core::DType::F32, &Device::Cpu).unwrap();
+        assert!((forward - expected).abs() < 1e-5);
+        assert!((loss_of(&model, None) - expected).abs() < 1e-5);
+        assert!((loss_of(&model, Some(&ones)) - expected).abs() < 1e-5);
+    }
+
+    // Smoothing mixes in the mean cost over the whole vocabulary, ln 28 - ln 2 / 27 for every target, which is
+    // more than the cost of these targets so the smoothed loss is higher.
+    #[test]
+    fn label_smoothing_mixes_in_the_uniform_cost() {
+        let unsmoothed = loss_of(&fixed_batch_model(PositionWeights::Uniform, 0.), None);
+        let smoothed = loss_of(&fixed_batch_model(PositionWeights::Uniform, 0.1), None);
+
+        let hard = (14f32.ln() + 2. * 28f32.ln()) / 3.;
+        let uniform = 28f32.ln() - 2f32.ln() / 27.;
+        assert!((unsmoothed - hard).abs() < 1e-5);
+        assert!((smoothed - (0.9 * hard + 0.1 * uniform)).abs() < 1e-5);
+        assert!(smoothed > unsmoothed);
+    }
+
+    // With the default block size of 3 the linear weights are 1/4, 2/4 and 3/4.
+    #[test]
+    fn linear_position_weights_take_a_weighted_mean() {
+        let model = fixed_batch_model(PositionWeights::Linear, 0.);
+        let weights = model.loss_weights().unwrap().unwrap().to_vec1::<f32>().unwrap();
+        assert_eq!(weights, vec![0.25, 0.5, 0.75]);
+
+        let expected = (14f32.ln() + 5. * 28f32.ln()) / 6.;
+        let weights = model.loss_weights().unwrap();
+        assert!((loss_of(&model, weights.as_ref()) - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn linear_position_weights_with_label_smoothing() {
+        let smoothing = 0.1;
+        let model = fixed_batch_model(PositionWeights::Linear, smoothing as f64);
+
+        let hard = (14f32.ln() + 5. * 28f32.ln()) / 6.;
+        let uniform = 28f32.ln() - 2f32.ln() / 27.;
+        let expected = (1. - smoothing) * hard + smoothing * uniform;
+        let weights = model.loss_weights().unwrap();
+        assert!((loss_of(&model, weights.as_ref()) - expected).abs() < 1e-5);
     }
-    Ok(())
 }
@@ -0,0 +1,62 @@
+use crate::{
+    app::{
+        message::{self, AppMessage, LossType, ModelResultMessage},
+        options::Options,
+    },
+    error::VibeError,
+    model::Model,
+};
+
+// Train a fresh model from each of the restarts without the interface, print the final validation loss of every
+// one, then save whichever ended lowest to the model file.
+//
+// NOTE: every restart starts from its own random draw of the parameters, a saved model is never loaded since
+// it would make all of them the same.
+pub fn run(options: &Options) -> Result<(), VibeError> {
+    let mut best: Option<(usize, f32, Model)> = None;
+
+    for restart in 1..=options.restarts {
+        let mut model = Model::init(options)?;
+        model.load_data()?;
+
+        let (sender, receiver) = message::create_data_channel();
+        model.train(options.iterations, 0, model.deadline(), &sender)?;
+
+        let validation_loss = receiver
+            .try_iter()
+            .filter_map(|message| match message {
+                AppMessage::Model(ModelResultMessage::Progress {
+                    loss_type: LossType::Validation,
+                    loss,
+                    ..
+                }) => Some(loss),
+                _ => None,
+            })
+            .last()
+            .ok_or_else(|| VibeError::new(format!("restart {} finished without a validation loss", restart)))?;
+        println!(
+            "restart {:>3}/{}: validation loss {:.4} {}",
+            restart,
+            options.restarts,
+            validation_loss,
+            options.units.name()
+        );
+
+        if best.as_ref().is_none_or(|(_, best_loss, _)| validation_loss < *best_loss) {
+            best = Some((restart, validation_loss, model));
+        }
+    }
+
+    if let Some((restart, validation_loss, mut model)) = best {
+        model.save()?;
+        println!(
+            "kept restart {} with validation loss {:.4} {}, saved to {}",
+            restart,
+            validation_loss,
+            options.units.name(),
+            options.model_file
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,254 @@
+use crate::error::VibeError;
+
+use candle_core::Tensor;
+use rand::{Rng, RngCore};
+
+pub const DEFAULT_SAMPLER: &str = SAMPLER_NAME_MULTINOMIAL;
+
+pub const SAMPLER_NAME_MULTINOMIAL: &str = "multinomial";
+pub const SAMPLER_NAME_GREEDY: &str = "greedy";
+pub const SAMPLER_NAME_TOP_K: &str = "top-k";
+pub const SAMPLER_NAME_TOP_P: &str = "top-p";
+pub const SAMPLER_NAME_TEMPERATURE: &str = "temperature";
+
+// A strategy for choosing the next token from the model's output distribution.
+pub trait Sampler {
+    // Choose an index from a probability tensor of shape (1, vocab).
+    fn choose(&self, probs: &Tensor, rng: &mut dyn RngCore) -> Result<usize, VibeError>;
+}
+
+// Sample proportionally to the probabilities.
+pub struct Multinomial;
+
+// Always take the most probable token.
+pub struct Greedy;
+
+// Sample from only the k most probable tokens.
+pub struct TopK {
+    pub k: usize,
+}
+
+// Sample from the smallest set of most probable tokens whose probabilities sum to at least p.
+pub struct TopP {
+    pub p: f32,
+}
+
+// Sample after sharpening (< 1) or flattening (> 1) the distribution.
+pub struct Temperature {
+    pub temperature: f32,
+}
+
+impl Sampler for Multinomial {
+    fn choose(&self, probs: &Tensor, rng: &mut dyn RngCore) -> Result<usize, VibeError> {
+        Ok(sample_weighted(&probabilities(probs)?, rng))
+    }
+}
+
+impl Sampler for Greedy {
+    fn choose(&self, probs: &Tensor, _rng: &mut dyn RngCore) -> Result<usize, VibeError> {
+        Ok(ranked(&probabilities(probs)?).first().map_or(0, |&(index, _)| index))
+    }
+}
+
+impl Sampler for TopK {
+    fn choose(&self, probs: &Tensor, rng: &mut dyn RngCore) -> Result<usize, VibeError> {
+        let mut weights = probabilities(probs)?;
+
+        for &(index, _) in ranked(&weights).iter().skip(self.k.max(1)) {
+            weights[index] = 0.;
+        }
+
+        Ok(sample_weighted(&weights, rng))
+    }
+}
+
+impl Sampler for TopP {
+    fn choose(&self, probs: &Tensor, rng: &mut dyn RngCore) -> Result<usize, VibeError> {
+        let mut weights = probabilities(probs)?;
+
+        // Keep tokens until the kept mass reaches p, always keeping at least the most probable one.
+        let mut kept_mass: f32 = 0.;
+        for (index, prob) in ranked(&weights) {
+            if kept_mass >= self.p {
+                weights[index] = 0.;
+            }
+            kept_mass += prob;
+        }
+
+        Ok(sample_weighted(&weights, rng))
+    }
+}
+
+impl Sampler for Temperature {
+    fn choose(&self, probs: &Tensor, rng: &mut dyn RngCore) -> Result<usize, VibeError> {
+        // Scaling the logits by 1/t before the softmax is the same as raising the probabilities to 1/t. It's done
+        // on log probabilities relative to the most probable token so low temperatures can't underflow every weight
+        // to zero, the most probable always keeps a weight of 1.
+        let probabilities = probabilities(probs)?;
+        let max = probabilities.iter().copied().fold(0., f32::max);
+        let weights: Vec<f32> = probabilities
+            .iter()
+            .map(|prob| ((prob.ln() - max.ln()) / self.temperature).exp())
+            .collect();
+
+        Ok(sample_weighted(&weights, rng))
+    }
+}
+
+// Build a sampler from its name, with the parameter after a colon where one is needed, e.g. "top-k:5".
+pub fn from_name(name: &str) -> Result<Box<dyn Sampler>, VibeError> {
+    let (kind, parameter) = match name.trim().split_once(':') {
+        Some((kind, parameter)) => (kind, Some(parameter)),
+        None => (name.trim(), None),
+    };
+
+    let missing = || VibeError::new(format!("the {} sampler needs a parameter, e.g. {}:<value>", kind, kind));
+
+    match kind {
+        SAMPLER_NAME_MULTINOMIAL => Ok(Box::new(Multinomial)),
+        SAMPLER_NAME_GREEDY => Ok(Box::new(Greedy)),
+        SAMPLER_NAME_TOP_K => Ok(Box::new(TopK {
+            k: str::parse::<usize>(parameter.ok_or_else(missing)?)?,
+        })),
+        SAMPLER_NAME_TOP_P => {
+            let p = str::parse::<f32>(parameter.ok_or_else(missing)?)?;
+            if !(p > 0. && p <= 1.) {
+                return Err(VibeError::new(format!("the top-p parameter must be in (0, 1]: {}", p)));
+            }
+            Ok(Box::new(TopP { p }))
+        }
+        SAMPLER_NAME_TEMPERATURE => {
+            let temperature = str::parse::<f32>(parameter.ok_or_else(missing)?)?;
+            if temperature <= 0. {
+                return Err(VibeError::new(format!("the temperature must be positive: {}", temperature)));
+            }
+            Ok(Box::new(Temperature { temperature }))
+        }
+        _ => Err(VibeError::new(format!("invalid sampler: {}", name))),
+    }
+}
+
+// Read a (1, vocab) probability tensor into a list.
+fn probabilities(probs: &Tensor) -> Result<Vec<f32>, VibeError> {
+    Ok(probs.flatten_all()?.to_vec1::<f32>()?)
+}
+
+// Pair each probability with its index, most probable first.
+fn ranked(weights: &[f32]) -> Vec<(usize, f32)> {
+    let mut ranked: Vec<(usize, f32)> = weights.iter().copied().enumerate().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranked
+}
+
+// Take a random sample from unnormalized weights.
+//
+// In order to take the distribution into account, a cumulative sum of the weights is computed and the first
+// index with a summed weight greater than a randomly chosen value is selected.
+fn sample_weighted(weights: &[f32], rng: &mut dyn RngCore) -> usize {
+    let total: f32 = weights.iter().sum();
+    let random_val: f32 = rng.random_range(0.0..1.0) * total;
+
+    let mut cumulative_sum: f32 = 0.;
+    for (index, &weight) in weights.iter().enumerate() {
+        cumulative_sum += weight;
+        if weight > 0. && random_val <= cumulative_sum {
+            return index;
+        }
+    }
+
+    // Rounding can leave the random value just past the final sum, fall back to the last possible token.
+    weights.iter().rposition(|&weight| weight > 0.).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candle_core::Device;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    const DRAWS: usize = 10000;
+
+    fn probs(values: &[f32]) -> Tensor {
+        Tensor::from_vec(values.to_vec(), (1, values.len()), &Device::Cpu).unwrap()
+    }
+
+    // How often each index is chosen over many draws.
+    fn frequencies(sampler: &dyn Sampler, values: &[f32]) -> Vec<f32> {
+        let probs = probs(values);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut counts = vec![0; values.len()];
+        for _ in 0..DRAWS {
+            counts[sampler.choose(&probs, &mut rng).unwrap()] += 1;
+        }
+
+        counts.iter().map(|&count| count as f32 / DRAWS as f32).collect()
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual - expected).abs() < 0.02, "{:?} vs {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn multinomial_follows_the_distribution() {
+        assert_close(&frequencies(&Multinomial, &[0.1, 0.2, 0.3, 0.4]), &[0.1, 0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn multinomial_never_picks_impossible_tokens() {
+        assert_close(&frequencies(&Multinomial, &[0., 0.5, 0., 0.5]), &[0., 0.5, 0., 0.5]);
+    }
+
+    #[test]
+    fn greedy_takes_the_most_probable() {
+        assert_close(&frequencies(&Greedy, &[0.2, 0.1, 0.4, 0.3]), &[0., 0., 1., 0.]);
+    }
+
+    #[test]
+    fn top_k_renormalizes_the_k_most_probable() {
+        assert_close(&frequencies(&TopK { k: 2 }, &[0.1, 0.2, 0.3, 0.4]), &[0., 0., 3. / 7., 4. / 7.]);
+    }
+
+    #[test]
+    fn top_p_keeps_tokens_until_the_mass_reaches_p() {
+        assert_close(&frequencies(&TopP { p: 0.5 }, &[0.6, 0.3, 0.1]), &[1., 0., 0.]);
+        assert_close(&frequencies(&TopP { p: 0.8 }, &[0.6, 0.3, 0.1]), &[2. / 3., 1. / 3., 0.]);
+    }
+
+    // Squaring [0.25, 0.75] gives weights 1 and 9, flattening with t = 2 gives 0.5 and ~0.866.
+    #[test]
+    fn temperature_sharpens_and_flattens() {
+        assert_close(&frequencies(&Temperature { temperature: 1. }, &[0.25, 0.75]), &[0.25, 0.75]);
+        assert_close(&frequencies(&Temperature { temperature: 0.5 }, &[0.25, 0.75]), &[0.1, 0.9]);
+        let flat = 0.5 / (0.5 + 0.75f32.sqrt());
+        assert_close(&frequencies(&Temperature { temperature: 2. }, &[0.25, 0.75]), &[flat, 1. - flat]);
+    }
+
+    // Raising ~0.037 to the power of 100 underflows, relative to the most probable token it stays exact.
+    #[test]
+    fn low_temperature_keeps_a_flat_distribution_flat() {
+        let flat = [1. / 27.; 27];
+        let frequencies = frequencies(&Temperature { temperature: 0.01 }, &flat);
+        assert!(frequencies[0] < 0.1, "{:?}", frequencies);
+        assert_close(&frequencies, &flat);
+    }
+
+    #[test]
+    fn low_temperature_takes_the_most_probable() {
+        assert_close(
+            &frequencies(&Temperature { temperature: 0.01 }, &[0.3, 0.2, 0.25, 0.25]),
+            &[1., 0., 0., 0.],
+        );
+    }
+
+    #[test]
+    fn from_name_checks_parameters() {
+        assert!(from_name("greedy").is_ok());
+        assert!(from_name(" top-k:5 ").is_ok());
+        assert!(from_name("top-k").is_err());
+        assert!(from_name("top-p:1.5").is_err());
+        assert!(from_name("temperature:0").is_err());
+        assert!(from_name("beam").is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{
+    app::{
+        message::{self, AppMessage, LossType, ModelResultMessage},
+        options::Options,
+    },
+    error::VibeError,
+    model::Model,
+};
+
+use std::time::Instant;
+
+// A couple hundred names built into the binary so the self test doesn't depend on any data files.
+const WORDS: &str = include_str!("../data/self_test.txt");
+
+// Small enough to train in a few seconds even on a cpu.
+const ITERATIONS: usize = 500;
+const HIDDEN_SIZE: usize = 100;
+const BATCH_SIZE: usize = 64;
+const SAMPLES: usize = 5;
+
+// The training loss in nats has to end up below this, a uniform guess over the characters is about 3.3.
+const LOSS_THRESHOLD: f32 = 3.0;
+
+// Train a small model on the built-in words on the chosen device, check the loss went down and that it can
+// generate, then print PASS or FAIL with how long each step took.
+pub fn run(options: &Options) -> Result<(), VibeError> {
+    let mut test_options = Options::new();
+    test_options.device = options.device.clone();
+    test_options.data = "the built-in self test words".to_string();
+    test_options.iterations = ITERATIONS;
+    test_options.hidden_size = HIDDEN_SIZE;
+    test_options.batch_size = BATCH_SIZE;
+
+    println!("self test on {}", test_options.device);
+
+    let start = Instant::now();
+    let mut model = Model::init(&test_options)?;
+    model.load_words(WORDS.lines().map(|word| word.trim().to_lowercase()).collect())?;
+    println!("\tinit     {:>8.2?}", start.elapsed());
+
+    let (sender, receiver) = message::create_data_channel();
+
+    let start = Instant::now();
+    model.train(ITERATIONS, 0, None, &sender)?;
+    let losses: Vec<f32> = receiver
+        .try_iter()
+        .filter_map(|message| match message {
+            AppMessage::Model(ModelResultMessage::Progress {
+                loss_type: LossType::Training,
+                loss,
+                ..
+            }) => Some(loss),
+            _ => None,
+        })
+        .collect();
+    println!("\ttrain    {:>8.2?}", start.elapsed());
+
+    let start = Instant::now();
+    model.generate(SAMPLES, &sender)?;
+    let samples: Vec<String> = receiver
+        .try_iter()
+        .filter_map(|message| match message {
+            AppMessage::Model(ModelResultMessage::Generated { text, .. }) => Some(text),
+            _ => None,
+        })
+        .collect();
+    println!("\tgenerate {:>8.2?}", start.elapsed());
+
+    // The last few losses are averaged since a single batch is noisy.
+    let first = losses.first().copied().unwrap_or(f32::NAN);
+    let tail = &losses[losses.len().saturating_sub(ITERATIONS / 10)..];
+    let last = tail.iter().sum::<f32>() / tail.len().max(1) as f32;
+    println!("\tloss {:.4} -> {:.4} (under {} to pass)", first, last, LOSS_THRESHOLD);
+    println!("\tsamples {}", samples.join(" "));
+
+    let mut failures = Vec::new();
+    if !(last < first && last < LOSS_THRESHOLD) {
+        failures.push(format!("the loss ended at {:.4}", last));
+    }
+    if samples.len() != SAMPLES {
+        failures.push(format!("generated {} of {} samples", samples.len(), SAMPLES));
+    }
+
+    if failures.is_empty() {
+        println!("PASS");
+        Ok(())
+    } else {
+        println!("FAIL");
+        Err(VibeError::new(format!("self test failed: {}", failures.join(", "))))
+    }
+}
@@ -0,0 +1,57 @@
+use crate::app::message::Layer;
+
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span},
+};
+
+// Draw the network as a column of boxes, one per layer, joined by arrows and annotated with their sizes and
+// parameter counts.
+pub fn diagram(layers: &[Layer]) -> Vec<Line<'static>> {
+    let width = layers
+        .iter()
+        .map(|layer| {
+            layer
+                .name
+                .chars()
+                .count()
+                .max(layer.size.chars().count())
+                .max(parameters_label(layer).chars().count())
+        })
+        .max()
+        .unwrap_or(0)
+        + 2;
+    let total: usize = layers.iter().map(|layer| layer.parameters).sum();
+
+    let mut lines: Vec<Line> = Vec::new();
+    for (index, layer) in layers.iter().enumerate() {
+        if index > 0 {
+            lines.push(Line::from(format!("{:^width$}", "│", width = width + 2)));
+            lines.push(Line::from(format!("{:^width$}", "▼", width = width + 2)));
+        }
+
+        lines.push(Line::from(format!("╭{}╮", "─".repeat(width))));
+        lines.push(Line::from(vec![
+            Span::raw("│ "),
+            Span::styled(
+                format!("{:<w$}", layer.name, w = width - 1),
+                Style::default().fg(Color::Blue).bold(),
+            ),
+            Span::raw("│"),
+        ]));
+        lines.push(Line::from(format!("│ {:<w$}│", layer.size, w = width - 1)));
+        if layer.parameters > 0 {
+            lines.push(Line::from(format!("│ {:<w$}│", parameters_label(layer), w = width - 1)));
+        }
+        lines.push(Line::from(format!("╰{}╯", "─".repeat(width))));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(format!("{} parameters total", total)));
+
+    lines
+}
+
+fn parameters_label(layer: &Layer) -> String {
+    format!("{} params", layer.parameters)
+}
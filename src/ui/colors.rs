@@ -1,4 +1,7 @@
-use ratatui::style::Color;
+use crate::error::VibeError;
+
+use ratatui::{buffer::Buffer, style::Color};
+use std::env;
 
 pub struct Palette {}
 
@@ -9,6 +12,31 @@ impl Palette {
 
     pub const TRAINING_LOSS_COLOR: Color = Color::Rgb(202, 255, 191); // #CAFFBF
     pub const VALIDATION_LOSS_COLOR: Color = Color::Rgb(189, 178, 255); // #BDB2FF
+    pub const EVALUATION_LOSS_COLOR: Color = Color::Rgb(255, 173, 173); // #FFADAD
+    pub const COMPARE_TRAINING_LOSS_COLOR: Color = Color::Rgb(155, 246, 255); // #9BF6FF
+    pub const COMPARE_VALIDATION_LOSS_COLOR: Color = Color::Rgb(255, 198, 255); // #FFC6FF
+    pub const LOSS_FLOOR_COLOR: Color = Color::Rgb(255, 255, 255); // #FFFFFF
+    pub const FINE_TUNE_COLOR: Color = Color::Rgb(160, 196, 255); // #A0C4FF
+    pub const LOSS_SPIKE_COLOR: Color = Color::Rgb(255, 99, 146); // #FF6392
+    pub const BASELINE_LOSS_COLOR: Color = Color::Rgb(108, 112, 134); // #6C7086
+
+    pub const CONFIDENT_COLOR: (u8, u8, u8) = (114, 240, 128); // #72F080
+    pub const UNLIKELY_COLOR: (u8, u8, u8) = (240, 96, 96); // #F06060
+
+    // Blend from the unlikely color at a probability of 0 to the confident color at 1.
+    //
+    // NOTE: most letters are sampled at well under even odds, the square root spreads those out instead of
+    // leaving nearly everything red.
+    pub fn confidence_color(probability: f32) -> Color {
+        let blend = probability.clamp(0., 1.).sqrt();
+        let channel = |unlikely: u8, confident: u8| (unlikely as f32 + (confident as f32 - unlikely as f32) * blend).round() as u8;
+
+        Color::Rgb(
+            channel(Self::UNLIKELY_COLOR.0, Self::CONFIDENT_COLOR.0),
+            channel(Self::UNLIKELY_COLOR.1, Self::CONFIDENT_COLOR.1),
+            channel(Self::UNLIKELY_COLOR.2, Self::CONFIDENT_COLOR.2),
+        )
+    }
 }
 
 pub enum Rainbow {
@@ -55,3 +83,127 @@ impl Rainbow {
         Color::Rgb(r, g, b)
     }
 }
+
+pub const COLOR_MODE_NAME_AUTO: &str = "auto";
+pub const COLOR_MODE_NAME_TRUECOLOR: &str = "truecolor";
+pub const COLOR_MODE_NAME_256: &str = "256";
+pub const COLOR_MODE_NAME_16: &str = "16";
+
+// The six channel levels of the xterm 256 color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+// The usual xterm values of the 16 ANSI colors, in index order.
+const ANSI_COLORS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+// How many colors the terminal can show. The palette is RGB, so on lesser terminals every drawn color is
+// mapped to the nearest one available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+    Auto,
+    Truecolor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorMode {
+    pub fn from_name(name: &str) -> Result<Self, VibeError> {
+        match name {
+            COLOR_MODE_NAME_AUTO => Ok(Self::Auto),
+            COLOR_MODE_NAME_TRUECOLOR => Ok(Self::Truecolor),
+            COLOR_MODE_NAME_256 => Ok(Self::Ansi256),
+            COLOR_MODE_NAME_16 => Ok(Self::Ansi16),
+            _ => Err(VibeError::new(format!("invalid color mode: {}", name))),
+        }
+    }
+
+    // Pick a concrete mode for auto from what the terminal advertises.
+    pub fn resolve(self) -> Self {
+        if self != Self::Auto {
+            return self;
+        }
+
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            Self::Truecolor
+        } else if term.contains("256color") {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    // Map every RGB color drawn into the buffer to the mode's nearest color.
+    pub fn downgrade(&self, buffer: &mut Buffer) {
+        if matches!(self, Self::Auto | Self::Truecolor) {
+            return;
+        }
+
+        for cell in buffer.content.iter_mut() {
+            cell.fg = self.convert(cell.fg);
+            cell.bg = self.convert(cell.bg);
+        }
+    }
+
+    fn convert(&self, color: Color) -> Color {
+        let Color::Rgb(r, g, b) = color else {
+            return color;
+        };
+
+        match self {
+            Self::Ansi256 => Color::Indexed(nearest_256(r, g, b)),
+            Self::Ansi16 => nearest_16(r, g, b),
+            Self::Auto | Self::Truecolor => color,
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let channel = |x: u8, y: u8| (i32::from(x) - i32::from(y)).unsigned_abs().pow(2);
+    channel(a.0, b.0) + channel(a.1, b.1) + channel(a.2, b.2)
+}
+
+// The nearest of the 6x6x6 color cube and the 24 step gray ramp.
+fn nearest_256(r: u8, g: u8, b: u8) -> u8 {
+    let level = |value: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|&index| (i32::from(CUBE_LEVELS[index]) - i32::from(value)).abs())
+            .unwrap_or(0)
+    };
+    let (r_index, g_index, b_index) = (level(r), level(g), level(b));
+    let cube = (CUBE_LEVELS[r_index], CUBE_LEVELS[g_index], CUBE_LEVELS[b_index]);
+
+    let average = (u32::from(r) + u32::from(g) + u32::from(b)) / 3;
+    let gray_index = (average.saturating_sub(8) / 10).min(23) as u8;
+    let gray_value = 8 + gray_index * 10;
+
+    if distance((gray_value, gray_value, gray_value), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_index
+    } else {
+        16 + 36 * r_index as u8 + 6 * g_index as u8 + b_index as u8
+    }
+}
+
+fn nearest_16(r: u8, g: u8, b: u8) -> Color {
+    ANSI_COLORS
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .map_or(Color::White, |(color, _)| *color)
+}
@@ -0,0 +1,62 @@
+use crate::ui::colors::Palette;
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::Line,
+    widgets::{Block, BorderType, Clear, Padding, Paragraph},
+};
+use std::collections::BTreeSet;
+
+// Draw how the latest generated batch differs from the pinned one over the main screen, in columns of the
+// words that are new, gone and kept.
+pub fn draw(frame: &mut Frame, pinned: &Option<Vec<String>>, latest: &[String]) -> Rect {
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let diff_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title("Vibe Diff");
+
+    frame.render_widget(Clear, area);
+
+    let Some(pinned) = pinned else {
+        frame.render_widget(Paragraph::new(Line::from("no vibes pinned yet")).block(diff_block), area);
+        return area;
+    };
+
+    let pinned: BTreeSet<&String> = pinned.iter().collect();
+    let latest: BTreeSet<&String> = latest.iter().collect();
+
+    let inner_area = diff_block.inner(area);
+    frame.render_widget(diff_block, area);
+
+    let columns = [
+        ("new", latest.difference(&pinned).collect::<Vec<_>>(), Color::LightGreen),
+        ("gone", pinned.difference(&latest).collect::<Vec<_>>(), Color::LightRed),
+        ("kept", pinned.intersection(&latest).collect::<Vec<_>>(), Palette::FG_COLOR),
+    ];
+
+    let column_areas = inner_area.layout_vec(&Layout::horizontal([Constraint::Fill(1); 3]).spacing(1));
+    for ((title, words, color), column_area) in columns.into_iter().zip(column_areas) {
+        let mut lines: Vec<Line> = vec![Line::styled(
+            format!("{} ({})", title, words.len()),
+            Style::default().fg(Color::Blue).bold(),
+        )];
+        lines.extend(
+            words
+                .into_iter()
+                .map(|word| Line::styled(word.to_string(), Style::default().fg(color))),
+        );
+        frame.render_widget(Paragraph::new(lines), column_area);
+    }
+
+    area
+}
@@ -1,28 +1,115 @@
-use crate::ui::colors::Palette;
+use crate::{app::message::GenerationSummary, ui::colors::Palette};
 
 use ratatui::{
     Frame,
-    layout::{Constraint, Flex, Layout},
+    layout::{Constraint, Flex, Layout, Rect},
     text::{Line, Span},
     widgets::{Block, BorderType, Clear, Padding, Paragraph},
 };
 
-pub fn draw(frame: &mut Frame, generated: &Vec<String>) {
+// Space between grid columns.
+const GRID_GAP: usize = 2;
+
+// Draw the generated strings over the main screen, newest first, with how many of the last batch were unique
+// and how they rate on novelty and likelihood. Each character is colored by how confident the model was in it.
+// Scrolling skips past the newest lines.
+pub fn draw(
+    frame: &mut Frame,
+    generated: &[String],
+    confidence: &[Vec<f32>],
+    summary: Option<GenerationSummary>,
+    grid: bool,
+    scroll: usize,
+) -> Rect {
     let area = frame.area();
     let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
     let horizontal = Layout::horizontal([Constraint::Percentage(40)]).flex(Flex::Center);
     let [area] = area.layout(&vertical);
     let [area] = area.layout(&horizontal);
+    if area.is_empty() {
+        return area;
+    }
 
-    let generated_block = Block::bordered()
+    let mut generated_block = Block::bordered()
         .border_type(BorderType::Rounded)
         .border_style(Palette::BORDER_COLOR)
         .padding(Padding::horizontal(1))
         .style((Palette::FG_COLOR, Palette::BG_COLOR))
-        .title("Vibes");
+        .title(match summary {
+            Some(summary) => {
+                let mut title = format!("Vibes ({}/{} unique", summary.unique, summary.count);
+                if summary.rejected > 0 {
+                    title.push_str(&format!(", {} filtered out", summary.rejected));
+                }
+                if summary.banned > 0 {
+                    title.push_str(&format!(", {} banned", summary.banned));
+                }
+                title + ")"
+            }
+            None => "Vibes".to_string(),
+        });
 
-    let lines: Vec<Line> = generated.iter().rev().map(|text| Line::from(vec![Span::raw(text)])).collect();
+    if let Some(summary) = summary
+        && let (Some(novel), Some(quality)) = (summary.novel, summary.quality())
+    {
+        generated_block = generated_block.title_bottom(format!(
+            "novel {}/{} x likelihood {:.3} = quality {:.3}",
+            novel, summary.count, summary.likelihood, quality
+        ));
+    }
 
     frame.render_widget(Clear, area);
-    frame.render_widget(Paragraph::new(lines).block(generated_block), area);
+
+    if grid {
+        let inner_area = generated_block.inner(area);
+        frame.render_widget(generated_block, area);
+        render_grid(frame, inner_area, generated, confidence, scroll);
+    } else {
+        let lines: Vec<Line> = generated
+            .iter()
+            .zip(confidence)
+            .rev()
+            .skip(scroll)
+            .map(|(text, confidence)| confidence_line(text, confidence))
+            .collect();
+        frame.render_widget(Paragraph::new(lines).block(generated_block), area);
+    }
+
+    area
+}
+
+// Render the strings in as many columns as fit, flowing left to right then top to bottom.
+fn render_grid(frame: &mut Frame, area: Rect, generated: &[String], confidence: &[Vec<f32>], scroll: usize) {
+    let column_width = generated.iter().map(|text| text.chars().count()).max().unwrap_or(0) + GRID_GAP;
+    let columns = (area.width as usize / column_width).max(1);
+
+    let column_areas = area.layout_vec(&Layout::horizontal(vec![Constraint::Length(column_width as u16); columns]));
+    for (column, column_area) in column_areas.into_iter().enumerate() {
+        let lines: Vec<Line> = generated
+            .iter()
+            .zip(confidence)
+            .rev()
+            .skip(scroll * columns + column)
+            .step_by(columns)
+            .map(|(text, confidence)| confidence_line(text, confidence))
+            .collect();
+        frame.render_widget(Paragraph::new(lines), column_area);
+    }
+}
+
+// Color each character by its probability, characters without one keep the popup's color.
+fn confidence_line<'a>(text: &'a str, confidence: &[f32]) -> Line<'a> {
+    let mut spans: Vec<Span> = text
+        .chars()
+        .zip(confidence)
+        .map(|(letter, probability)| match probability {
+            probability if probability.is_nan() => Span::raw(letter.to_string()),
+            probability => Span::styled(letter.to_string(), Palette::confidence_color(*probability)),
+        })
+        .collect();
+
+    let colored: usize = text.chars().take(confidence.len()).map(char::len_utf8).sum();
+    spans.push(Span::raw(&text[colored..]));
+
+    Line::from(spans)
 }
@@ -0,0 +1,79 @@
+use crate::{app::message::Histogram, ui::colors::Palette};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Bar heights from an empty bucket up to the fullest one.
+const LEVELS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// Width of the parameter names column.
+const NAME_WIDTH: usize = 15;
+
+// Draw the latest weight and gradient histograms of each parameter over the main screen.
+pub fn draw(frame: &mut Frame, histograms: &Option<(usize, Vec<Histogram>, Vec<Histogram>)>) -> Rect {
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Length(10)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let histogram_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title(match histograms {
+            Some((iteration, _, _)) => format!("Histograms (iteration {})", iteration),
+            None => "Histograms".to_string(),
+        });
+
+    frame.render_widget(Clear, area);
+
+    let Some((_, weights, gradients)) = histograms else {
+        frame.render_widget(Paragraph::new(Line::from("waiting for training...")).block(histogram_block), area);
+        return area;
+    };
+
+    let mut lines = vec![Line::from(vec![
+        Span::raw(format!("{:<NAME_WIDTH$}", "")),
+        Span::styled(format!("{:<44}", "weights"), Style::default().fg(Color::Blue).bold()),
+        Span::styled("gradients", Style::default().fg(Color::Blue).bold()),
+    ])];
+
+    // Frozen parameters aren't updated so they have no gradient.
+    for weight in weights {
+        let mut spans = vec![Span::raw(format!("{:<NAME_WIDTH$}", weight.name))];
+        spans.extend(histogram_spans(weight, Palette::TRAINING_LOSS_COLOR));
+        spans.push(Span::raw("  "));
+        match gradients.iter().find(|gradient| gradient.name == weight.name) {
+            Some(gradient) => spans.extend(histogram_spans(gradient, Palette::VALIDATION_LOSS_COLOR)),
+            None => spans.push(Span::raw("frozen")),
+        }
+        lines.push(Line::from(spans));
+    }
+
+    frame.render_widget(Paragraph::new(lines).block(histogram_block), area);
+
+    area
+}
+
+// One histogram as a row of bars between its min and max.
+fn histogram_spans(histogram: &Histogram, color: Color) -> Vec<Span<'static>> {
+    let fullest = histogram.counts.iter().copied().max().unwrap_or(0).max(1);
+    let bars: String = histogram
+        .counts
+        .iter()
+        .map(|&count| LEVELS[(count * (LEVELS.len() - 1)).div_ceil(fullest)])
+        .collect();
+
+    vec![
+        Span::raw(format!("{:>8.3} ", histogram.min)),
+        Span::styled(bars, Style::default().fg(color)),
+        Span::raw(format!(" {:<8.3}", histogram.max)),
+    ]
+}
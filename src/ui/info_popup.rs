@@ -0,0 +1,54 @@
+use crate::{
+    app::message::Layer,
+    ui::{architecture, colors::Palette},
+};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Draw the model description over the main screen, next to a diagram of the network.
+pub fn draw(frame: &mut Frame, description: &Option<(String, Vec<Layer>)>) -> Rect {
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Percentage(70)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let info_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title("Model Info");
+
+    let lines: Vec<Line> = match description {
+        Some((description, _)) => description
+            .lines()
+            .map(|line| match line.split_once('=') {
+                Some((key, value)) => Line::from(vec![
+                    Span::styled(format!("{}=", key), Style::default().fg(Color::Blue).bold()),
+                    Span::raw(value.to_string()),
+                ]),
+                None => Line::from(line.to_string()),
+            })
+            .collect(),
+        None => vec![Line::from("waiting for the model...")],
+    };
+
+    let inner_area = info_block.inner(area);
+    let [description_area, diagram_area] = inner_area.layout(&Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(info_block, area);
+    frame.render_widget(Paragraph::new(lines), description_area);
+    if let Some((_, layers)) = description {
+        frame.render_widget(Paragraph::new(architecture::diagram(layers)), diagram_area);
+    }
+
+    area
+}
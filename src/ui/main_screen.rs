@@ -1,32 +1,129 @@
-use crate::app::{device, options::Options};
-use crate::ui::{colors::Palette, generate_popup, logo};
+use crate::app::{
+    app::State,
+    device,
+    keys::{self, Action},
+    message::{GenerationStep, GenerationSummary, Histogram, Layer, PositionLoss, ScoreStep},
+    options::Options,
+};
+use crate::ui::{
+    colors::Palette, diff_popup, generate_popup, histogram_popup, info_popup, logo, position_popup, probe_popup, score_popup, step_popup,
+    stream_screen,
+};
 
 use ratatui::{
     Frame,
-    layout::{Alignment, Constraint, Layout, Rect, Spacing},
+    layout::{Alignment, Constraint, Flex, Layout, Rect, Spacing},
     style::{Color, Style, Stylize},
     symbols::Marker,
     text::{Line, Span},
     widgets::{Axis, Block, BorderType, Chart, Dataset, GraphType, Padding, Paragraph},
 };
+use std::time::{SystemTime, UNIX_EPOCH};
 
-// Draw the main screen showing the options and model training statistics with dynamic loss data.
-pub fn draw(
-    frame: &mut Frame,
-    options: &Options,
-    loss_data: &Vec<(f64, f64)>,
-    validation_loss_data: &Vec<(f64, f64)>,
-    generated: &Vec<String>,
-    show_generated: bool,
-) {
+// The share of saturated hidden activations past which the chart flags it.
+const SATURATION_WARNING: f32 = 0.2;
+
+// Frames of the spinner shown while the model initializes.
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+// Everything the loss chart shows, borrowed from the app for a frame.
+#[derive(Clone, Copy)]
+pub struct LossChart<'a> {
+    pub loss_data: &'a [(f64, f64)],
+    pub validation_loss_data: &'a [(f64, f64)],
+    pub evaluation_loss_data: &'a [(f64, f64)],
+    pub baseline_loss_data: &'a [(f64, f64)],
+    pub compare_loss_data: &'a [(f64, f64)],
+    pub compare_validation_loss_data: &'a [(f64, f64)],
+    pub loss_floor: Option<f32>,
+    pub fine_tune_start: Option<usize>,
+    pub loss_spikes: &'a [(f64, f64)],
+    pub saturation: Option<(usize, f32)>,
+    pub smoothing: usize,
+}
+
+// Everything the main screen and its popups show, borrowed from the app for a frame.
+#[derive(Clone, Copy)]
+pub struct MainView<'a> {
+    pub state: &'a State,
+    pub train_target: usize,
+    pub throughput: Option<f64>,
+    pub batch_size: usize,
+    pub options: &'a Options,
+    pub chart: LossChart<'a>,
+    pub generated: &'a [String],
+    pub generated_confidence: &'a [Vec<f32>],
+    pub generation_summary: Option<GenerationSummary>,
+    pub generation_step: &'a Option<GenerationStep>,
+    pub show_generated: bool,
+    pub grid_generated: bool,
+    pub latest_batch: &'a [String],
+    pub pinned_batch: &'a Option<Vec<String>>,
+    pub show_diff: bool,
+    pub description: &'a Option<(String, Vec<Layer>)>,
+    pub show_info: bool,
+    pub score: &'a Option<(String, Vec<ScoreStep>)>,
+    pub show_score: bool,
+    pub position_losses: &'a Option<Vec<PositionLoss>>,
+    pub show_positions: bool,
+    pub probe: &'a Option<(String, Vec<(String, f32)>)>,
+    pub show_probe: bool,
+    pub histograms: &'a Option<(usize, Vec<Histogram>, Vec<Histogram>)>,
+    pub show_histograms: bool,
+    pub live_sample: &'a Option<(usize, String)>,
+    pub generated_scroll: usize,
+    pub status: &'a Option<String>,
+    pub error: &'a Option<String>,
+}
+
+// Draw the main screen showing the options and model training statistics with dynamic loss data, returning where
+// each keybinding was drawn so it can be clicked.
+pub fn draw(frame: &mut Frame, view: &MainView) -> Vec<(Rect, Action)> {
+    let &MainView {
+        state,
+        train_target,
+        throughput,
+        batch_size,
+        options,
+        chart,
+        generated,
+        generated_confidence,
+        generation_summary,
+        generation_step,
+        show_generated,
+        grid_generated,
+        latest_batch,
+        pinned_batch,
+        show_diff,
+        description,
+        show_info,
+        score,
+        show_score,
+        position_losses,
+        show_positions,
+        probe,
+        show_probe,
+        histograms,
+        show_histograms,
+        live_sample,
+        generated_scroll,
+        status,
+        error,
+    } = view;
     let area = frame.area();
 
+    // NOTE: a terminal shrunk to nothing mid resize has no room for anything, and the charts don't cope with
+    // zero sized areas.
+    if area.is_empty() {
+        return Vec::new();
+    }
+
     frame.buffer_mut().set_style(area, (Palette::FG_COLOR, Palette::BG_COLOR));
 
-    let main_layout = Layout::vertical([Constraint::Length(1), Constraint::Min(1)]);
+    let main_layout = Layout::vertical([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)]);
     let content_layout = Layout::horizontal([Constraint::Length(30), Constraint::Fill(1)]);
 
-    let [title_area, main_area] = main_layout.areas(area);
+    let [title_area, main_area, status_bar_area] = main_layout.areas(area);
     let [config_area, model_area] = content_layout.areas(main_area);
 
     let title = Block::new()
@@ -59,12 +156,16 @@ pub fn draw(
             Span::styled("generate=", Style::default().fg(Color::Blue).bold()),
             Span::raw(options.generate.to_string()),
         ]),
+        Line::from(vec![
+            Span::styled("sampler=", Style::default().fg(Color::Blue).bold()),
+            Span::raw(options.sampler.clone()),
+        ]),
     ];
 
     let parameters_lines: Vec<Line> = vec![
         Line::from(vec![
             Span::styled("batch_size=", Style::default().fg(Color::Blue).bold()),
-            Span::raw(options.batch_size.to_string()),
+            Span::raw(batch_size.to_string()),
         ]),
         Line::from(vec![
             Span::styled("block_size=", Style::default().fg(Color::Blue).bold()),
@@ -84,31 +185,61 @@ pub fn draw(
         ]),
     ];
 
-    let keybinding_lines: Vec<Line> = vec![
-        Line::from(vec![
-            Span::raw("t -> "),
-            Span::styled("train", Style::default().fg(Color::LightGreen).bold()),
-        ]),
-        Line::from(vec![
-            Span::raw("v -> "),
-            Span::styled("vibe strings", Style::default().fg(Color::LightGreen).bold()),
-        ]),
-        Line::from(vec![
-            Span::raw("p -> "),
-            Span::styled(
-                if show_generated { "hide vibes" } else { "show vibes" },
-                Style::default().fg(Color::LightGreen).bold(),
-            ),
-        ]),
-        Line::from(vec![
-            Span::raw("s -> "),
-            Span::styled("save model", Style::default().fg(Color::LightGreen).bold()),
-        ]),
-        Line::from(vec![
-            Span::raw("q/esc -> "),
-            Span::styled("quit", Style::default().fg(Color::Red).bold()),
-        ]),
-    ];
+    let keybinding_lines: Vec<Line> = options
+        .key_bindings
+        .bindings()
+        .iter()
+        .map(|(action, bound_keys)| {
+            let (label, color) = match action {
+                Action::Train => ("train", Color::LightGreen),
+                Action::Vibe => ("vibe strings", Color::LightGreen),
+                Action::Stream => ("stream vibes", Color::LightGreen),
+                Action::Cancel => ("cancel vibes", Color::LightGreen),
+                Action::Reload => ("reload data", Color::LightGreen),
+                Action::Popup => (if show_generated { "hide vibes" } else { "show vibes" }, Color::LightGreen),
+                Action::Grid => (if grid_generated { "list vibes" } else { "grid vibes" }, Color::LightGreen),
+                Action::Pin => ("pin vibes", Color::LightGreen),
+                Action::Diff => (if show_diff { "hide vibe diff" } else { "diff vibes" }, Color::LightGreen),
+                Action::Score => (if show_score { "hide score" } else { "score word" }, Color::LightGreen),
+                Action::Probe => (if show_probe { "hide probe" } else { "probe context" }, Color::LightGreen),
+                Action::Positions => (
+                    if show_positions { "hide position loss" } else { "loss by position" },
+                    Color::LightGreen,
+                ),
+                Action::Histograms => (
+                    if show_histograms { "hide histograms" } else { "show histograms" },
+                    Color::LightGreen,
+                ),
+                Action::Floor => (
+                    if chart.loss_floor.is_some() {
+                        "hide loss floor"
+                    } else {
+                        "show loss floor"
+                    },
+                    Color::LightGreen,
+                ),
+                Action::Smooth => ("smooth loss", Color::LightGreen),
+                Action::Unsmooth => ("unsmooth loss", Color::LightGreen),
+                Action::Info => (if show_info { "hide model info" } else { "show model info" }, Color::LightGreen),
+                Action::Device => (
+                    if options.device == device::DEVICE_NAME_CPU {
+                        "switch to gpu"
+                    } else {
+                        "switch to cpu"
+                    },
+                    Color::LightGreen,
+                ),
+                Action::Save => ("save model", Color::LightGreen),
+                Action::Quit => ("quit", Color::Red),
+            };
+            let key_names: Vec<String> = bound_keys.iter().map(keys::format_key).collect();
+
+            Line::from(vec![
+                Span::raw(format!("{} -> ", key_names.join("/"))),
+                Span::styled(label, Style::default().fg(color).bold()),
+            ])
+        })
+        .collect();
 
     let config_layout = Layout::vertical([Constraint::Length(8), Constraint::Fill(1)]).spacing(Spacing::Space(1));
     let [logo_area, lower_config_area] = config_area.layout(&config_layout);
@@ -144,70 +275,428 @@ pub fn draw(
         .padding(Padding::horizontal(1))
         .title("Keybindings");
 
+    // The panel doesn't wrap, so each binding is a single row cut off at the panel's edge, and only the text
+    // of the row can be clicked.
+    let keybinding_inner = keybinding_block.inner(keybinding_area);
+    let mut click_targets: Vec<(Rect, Action)> = options
+        .key_bindings
+        .bindings()
+        .iter()
+        .zip(&keybinding_lines)
+        .zip(keybinding_inner.rows())
+        .map(|(((action, _), line), row)| {
+            (
+                Rect {
+                    width: row.width.min(line.width() as u16),
+                    ..row
+                },
+                *action,
+            )
+        })
+        .collect();
+
     frame.render_widget(Paragraph::new(keybinding_lines).block(keybinding_block), keybinding_area);
 
-    render_loss(frame, model_area, options, loss_data, validation_loss_data);
+    // Make room under the chart for any status or error message.
+    let model_area = if status.is_some() || error.is_some() {
+        let [chart_area, message_area] = model_area.layout(&Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]));
+        render_message(frame, message_area, status, error);
+        chart_area
+    } else {
+        model_area
+    };
+
+    // The latest sample sits under the chart.
+    let model_area = match live_sample {
+        Some((iteration, text)) => {
+            let [chart_area, sample_area] = model_area.layout(&Layout::vertical([Constraint::Fill(1), Constraint::Length(3)]));
+            render_live_sample(frame, sample_area, *iteration, text);
+            chart_area
+        }
+        None => model_area,
+    };
+
+    // A narrow terminal can squeeze the chart out entirely.
+    if !model_area.is_empty() {
+        if *state == State::Initializing {
+            render_initializing(frame, model_area);
+        } else {
+            render_loss(frame, model_area, options, &chart);
+        }
+    }
+
+    render_status_bar(frame, status_bar_area, state, train_target, throughput, options, chart.loss_data);
+
+    // Where the popups are drawn over the panel, the bindings under them can't be clicked.
+    let mut covered = Vec::new();
 
     if show_generated {
-        generate_popup::draw(frame, generated);
+        covered.push(generate_popup::draw(
+            frame,
+            generated,
+            generated_confidence,
+            generation_summary,
+            grid_generated,
+            generated_scroll,
+        ));
+    }
+
+    if show_diff {
+        covered.push(diff_popup::draw(frame, pinned_batch, latest_batch));
     }
+
+    if let Some(step) = generation_step {
+        covered.push(step_popup::draw(frame, step));
+    }
+
+    if show_score {
+        covered.push(score_popup::draw(frame, score, options.score_context));
+    }
+
+    if show_probe {
+        covered.push(probe_popup::draw(frame, probe));
+    }
+
+    if show_histograms {
+        covered.push(histogram_popup::draw(frame, histograms));
+    }
+
+    if show_positions {
+        covered.push(position_popup::draw(frame, position_losses, options.units.name()));
+    }
+
+    if show_info {
+        covered.push(info_popup::draw(frame, description));
+    }
+
+    if *state == State::Stream {
+        covered.push(stream_screen::draw(frame, generated));
+    }
+
+    click_targets.retain(|(target, _)| covered.iter().all(|area| !area.intersects(*target)));
+    click_targets
+}
+
+// Render a single line summary of what the app is doing and the active generation modes.
+fn render_status_bar(
+    frame: &mut Frame,
+    area: Rect,
+    state: &State,
+    train_target: usize,
+    throughput: Option<f64>,
+    options: &Options,
+    loss_data: &[(f64, f64)],
+) {
+    let (iteration, loss) = match loss_data.last() {
+        Some((iteration, loss)) => (*iteration as usize, format!("{:.4}", loss)),
+        None => (0, "-".to_string()),
+    };
+
+    let mut modes: Vec<String> = vec![
+        format!("sampler={}", options.sampler),
+        format!("tokenizer={}", options.tokenizer.name()),
+    ];
+    if options.target_length > 0 {
+        modes.push(format!("target_length={}", options.target_length));
+    }
+    if !options.prefix.is_empty() {
+        modes.push(format!("prefix={}", options.prefix));
+    }
+    if options.filter_regex.is_some() {
+        modes.push("filtered".to_string());
+    }
+
+    let separator = Span::styled(" | ", Style::default().fg(Palette::BORDER_COLOR));
+    let line = Line::from(vec![
+        Span::styled(state.name(), Style::default().fg(Color::LightGreen).bold()),
+        separator.clone(),
+        Span::raw(options.device.clone()),
+        separator.clone(),
+        Span::raw(format!("iteration {}/{}", iteration, train_target.max(iteration))),
+        separator.clone(),
+        Span::raw(match throughput {
+            Some(throughput) => format!("{:.0} examples/s", throughput),
+            None => "- examples/s".to_string(),
+        }),
+        separator.clone(),
+        Span::raw(format!("loss {} {}", loss, options.units.name())),
+        separator,
+        Span::raw(modes.join(" ")),
+    ]);
+
+    frame.render_widget(Paragraph::new(line), area);
+}
+
+// Render the latest message from the model, errors take precedence over status updates.
+fn render_message(frame: &mut Frame, area: Rect, status: &Option<String>, error: &Option<String>) {
+    let (title, text, color) = match (error, status) {
+        (Some(error), _) => ("Error", error.clone(), Color::Red),
+        (None, Some(status)) => ("Status", status.clone(), Palette::BORDER_COLOR),
+        (None, None) => return,
+    };
+
+    let message_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(color)
+        .padding(Padding::horizontal(1))
+        .title(title);
+
+    frame.render_widget(Paragraph::new(text).block(message_block), area);
+}
+
+// Render the word sampled most recently while training.
+fn render_live_sample(frame: &mut Frame, area: Rect, iteration: usize, text: &str) {
+    let sample_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .title(format!("Latest Sample (iteration {})", iteration));
+
+    frame.render_widget(
+        Paragraph::new(text.to_string())
+            .style(Palette::TRAINING_LOSS_COLOR)
+            .block(sample_block),
+        area,
+    );
+}
+
+// The points of a series from iteration x onwards, the series are in iteration order.
+fn since(data: &[(f64, f64)], x: f64) -> &[(f64, f64)] {
+    &data[data.partition_point(|point| point.0 < x)..]
+}
+
+// Render a spinner in place of the chart until the model is ready.
+//
+// NOTE: the frame is picked from the clock so the spinner doesn't need any state, the app redraws often enough
+// while initializing to animate it.
+fn render_initializing(frame: &mut Frame, area: Rect) {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis());
+    let spinner = SPINNER[(millis / 100) as usize % SPINNER.len()];
+
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .title(Line::from("Loss").cyan().bold().centered());
+    let [text_area] = block
+        .inner(area)
+        .layout(&Layout::vertical([Constraint::Length(1)]).flex(Flex::Center));
+
+    frame.render_widget(block, area);
+    frame.render_widget(
+        Paragraph::new(Line::from(format!("{} initializing model...", spinner)).centered()),
+        text_area,
+    );
+}
+
+// Centered moving average of a series over a window of points, shrinking the window at the ends.
+fn smooth(data: &[(f64, f64)], window: usize) -> Vec<(f64, f64)> {
+    let half = window / 2;
+    let mut sums = vec![0.; data.len() + 1];
+    for (index, (_, loss)) in data.iter().enumerate() {
+        sums[index + 1] = sums[index] + loss;
+    }
+
+    data.iter()
+        .enumerate()
+        .map(|(index, &(iteration, _))| {
+            let (start, end) = (index.saturating_sub(half), (index + half + 1).min(data.len()));
+            (iteration, (sums[end] - sums[start]) / (end - start) as f64)
+        })
+        .collect()
 }
 
 // Render the loss chart with dynamic data.
-fn render_loss(frame: &mut Frame, area: Rect, options: &Options, loss_data: &[(f64, f64)], validation_loss_data: &[(f64, f64)]) {
-    // Use either dynamic data or default data
-    let training_data = loss_data.to_vec();
+fn render_loss(frame: &mut Frame, area: Rect, options: &Options, chart: &LossChart) {
+    let &LossChart {
+        loss_data,
+        validation_loss_data,
+        evaluation_loss_data,
+        baseline_loss_data,
+        compare_loss_data,
+        compare_validation_loss_data,
+        loss_floor,
+        fine_tune_start,
+        loss_spikes,
+        saturation,
+        smoothing,
+    } = chart;
 
-    let validation_data = validation_loss_data.to_vec();
+    // Only chart the series from the oldest of the most recent training points in the window, the full history
+    // is still kept for the loss log.
+    let min_x = match options.chart_window {
+        0 => 0.,
+        window => loss_data.len().checked_sub(window).map_or(0., |start| loss_data[start].0),
+    };
+    let training_data = smooth(since(loss_data, min_x), smoothing);
+    let validation_data = since(validation_loss_data, min_x);
+    let evaluation_loss_data = since(evaluation_loss_data, min_x);
+    let baseline_loss_data = since(baseline_loss_data, min_x);
+    let compare_loss_data = smooth(since(compare_loss_data, min_x), smoothing);
+    let compare_validation_loss_data = since(compare_validation_loss_data, min_x);
+    let loss_spikes = since(loss_spikes, min_x);
 
-    let max_x = if let Some(elem) = loss_data.last() {
-        if elem.0 > options.iterations as f64 {
-            (elem.0 / 100.).ceil() * 100.
-        } else {
-            options.iterations as f64
-        }
+    // Stretch the x axis to fit whichever of the current, comparison or baseline runs is longest.
+    let last_x = loss_data
+        .last()
+        .map_or(0., |elem| elem.0)
+        .max(baseline_loss_data.last().map_or(0., |elem| elem.0))
+        .max(compare_loss_data.last().map_or(0., |elem| elem.0));
+    let max_x = if last_x > options.iterations as f64 {
+        (last_x / 100.).ceil() * 100.
     } else {
         options.iterations as f64
     };
 
-    let max_y = 4.;
-    let min_y = 2.;
+    // Scale the nats based bounds to the reported units, raising the top to fit a floor or spike above them. The
+    // axis starts at zero so neither falls under it.
+    let scale = options.units.scale() as f64;
+    let max_y = loss_spikes
+        .iter()
+        .map(|(_, loss)| *loss)
+        .chain(loss_floor.map(f64::from))
+        .fold(4. * scale, f64::max)
+        .ceil();
+
+    let mut datasets = Vec::new();
 
-    let datasets = vec![
+    // The baseline goes first so the current run is drawn on top of it.
+    if !baseline_loss_data.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("Baseline Loss")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::BASELINE_LOSS_COLOR)
+                .data(baseline_loss_data),
+        );
+    }
+
+    datasets.push(
         Dataset::default()
             .name("Training Loss")
             .marker(Marker::Braille)
             .graph_type(GraphType::Scatter)
             .style(Palette::TRAINING_LOSS_COLOR)
             .data(&training_data),
-        Dataset::default()
-            .name("Validation Loss")
-            .marker(Marker::Dot)
-            .graph_type(GraphType::Scatter)
-            .style(Palette::VALIDATION_LOSS_COLOR)
-            .data(&validation_data),
-    ];
+    );
+
+    if !options.no_validation {
+        datasets.push(
+            Dataset::default()
+                .name("Validation Loss")
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::VALIDATION_LOSS_COLOR)
+                .data(validation_data),
+        );
+    }
+
+    if let Some(flags) = &options.compare_flags {
+        datasets.extend([
+            Dataset::default()
+                .name(format!("Training Loss ({})", flags))
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::COMPARE_TRAINING_LOSS_COLOR)
+                .data(&compare_loss_data),
+            Dataset::default()
+                .name(format!("Validation Loss ({})", flags))
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::COMPARE_VALIDATION_LOSS_COLOR)
+                .data(compare_validation_loss_data),
+        ]);
+    }
+
+    if !evaluation_loss_data.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("Evaluation Loss")
+                .marker(Marker::Dot)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::EVALUATION_LOSS_COLOR)
+                .data(evaluation_loss_data),
+        );
+    }
+
+    // Spikes are marked at the raw loss, even when the training loss is drawn smoothed.
+    if !loss_spikes.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("Loss Spikes")
+                .marker(Marker::Block)
+                .graph_type(GraphType::Scatter)
+                .style(Palette::LOSS_SPIKE_COLOR)
+                .data(loss_spikes),
+        );
+    }
+
+    // The floor is a line across the whole chart, drawn last so it stays visible.
+    let floor_data: Vec<(f64, f64)> = loss_floor.map_or(Vec::new(), |loss| vec![(min_x, loss as f64), (max_x, loss as f64)]);
+    if !floor_data.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("Loss Floor")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Palette::LOSS_FLOOR_COLOR)
+                .data(&floor_data),
+        );
+    }
+
+    // Fine-tuning starts at a line up the whole chart.
+    let fine_tune_data: Vec<(f64, f64)> = fine_tune_start
+        .map(|iteration| iteration as f64)
+        .filter(|x| *x >= min_x)
+        .map_or(Vec::new(), |x| vec![(x, 0.), (x, max_y)]);
+    if !fine_tune_data.is_empty() {
+        datasets.push(
+            Dataset::default()
+                .name("Fine-tuning")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Palette::FINE_TUNE_COLOR)
+                .data(&fine_tune_data),
+        );
+    }
+
+    let x_labels = vec![min_x.to_string(), max_x.to_string()];
+    let y_labels = vec![0f64.to_string(), max_y.to_string()];
 
-    let x_labels = vec!["0".to_string(), max_x.to_string()];
-    let y_labels = vec![min_y.to_string(), max_y.to_string()];
+    let mut block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .style(Style::default().fg(Palette::FG_COLOR))
+        .title(Line::from("Loss").cyan().bold().centered());
+
+    // Saturation is flagged once enough of the hidden layer is stuck in tanh's tails to slow training down.
+    if let Some((iteration, fraction)) = saturation {
+        let color = if fraction > SATURATION_WARNING {
+            Palette::EVALUATION_LOSS_COLOR
+        } else {
+            Palette::FG_COLOR
+        };
+        block = block.title_bottom(
+            Line::from(format!("tanh saturation {:.1}% (iteration {})", fraction * 100., iteration))
+                .style(color)
+                .right_aligned(),
+        );
+    }
 
     let chart = Chart::new(datasets)
         .style(Style::default().fg(Palette::FG_COLOR).bg(Palette::BG_COLOR))
-        .block(
-            Block::bordered()
-                .border_type(BorderType::Rounded)
-                .border_style(Palette::BORDER_COLOR)
-                .style(Style::default().fg(Palette::FG_COLOR))
-                .title(Line::from("Loss").cyan().bold().centered()),
-        )
+        .block(block)
         .x_axis(
             Axis::default()
-                .bounds([0., max_x])
+                .bounds([min_x, max_x])
                 .style(Style::default().fg(Palette::FG_COLOR))
                 .labels(x_labels),
         )
         .y_axis(
             Axis::default()
+                .title(options.units.name())
                 .bounds([0., max_y])
                 .style(Style::default().fg(Palette::FG_COLOR))
                 .labels(y_labels),
@@ -1,4 +1,13 @@
+pub mod architecture;
 pub mod colors;
+pub mod diff_popup;
 pub mod generate_popup;
+pub mod histogram_popup;
+pub mod info_popup;
 pub mod logo;
 pub mod main_screen;
+pub mod position_popup;
+pub mod probe_popup;
+pub mod score_popup;
+pub mod step_popup;
+pub mod stream_screen;
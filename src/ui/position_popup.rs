@@ -0,0 +1,58 @@
+use crate::{app::message::PositionLoss, ui::colors::Palette};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    text::Line,
+    widgets::{Bar, BarChart, Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Width of each bar, enough for the loss printed on it.
+const BAR_WIDTH: u16 = 5;
+
+// Draw the average validation loss at each position within words as a bar chart over the main screen.
+pub fn draw(frame: &mut Frame, losses: &Option<Vec<PositionLoss>>, units: &str) -> Rect {
+    let bar_count = losses.as_ref().map(|losses| losses.len()).unwrap_or(0).max(1) as u16;
+
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Length(bar_count * (BAR_WIDTH + 1) + 3)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let position_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title(format!("Loss by Position ({})", units));
+
+    frame.render_widget(Clear, area);
+
+    let Some(losses) = losses else {
+        frame.render_widget(Paragraph::new(Line::from("measuring...")).block(position_block), area);
+        return area;
+    };
+
+    // Bars are labeled with the position counting from 1.
+    let bars: Vec<Bar> = losses
+        .iter()
+        .enumerate()
+        .map(|(position, loss)| {
+            Bar::new((loss.loss * 1000.).round() as u64)
+                .label((position + 1).to_string())
+                .text_value(format!("{:.2}", loss.loss))
+                .style(Palette::VALIDATION_LOSS_COLOR)
+        })
+        .collect();
+
+    let total: usize = losses.iter().map(|loss| loss.count).sum();
+    let chart = BarChart::vertical(bars)
+        .bar_width(BAR_WIDTH)
+        .bar_gap(1)
+        .block(position_block.title_bottom(format!("{} validation targets", total)));
+
+    frame.render_widget(chart, area);
+
+    area
+}
@@ -0,0 +1,72 @@
+use crate::ui::colors::Palette;
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Width of the probability bar at 100%.
+const BAR_WIDTH: usize = 20;
+
+// Draw the ranked next token distribution after the probed context over the main screen, the most likely
+// tokens on the left and the least likely on the right.
+pub fn draw(frame: &mut Frame, probe: &Option<(String, Vec<(String, f32)>)>) -> Rect {
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let probe_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title(match probe {
+            Some((context, _)) => format!("Probe: {}_", context),
+            None => "Probe".to_string(),
+        });
+
+    frame.render_widget(Clear, area);
+
+    let Some((_, distribution)) = probe else {
+        frame.render_widget(Paragraph::new(Line::from("waiting for the model...")).block(probe_block), area);
+        return area;
+    };
+
+    let inner_area = probe_block.inner(area);
+    frame.render_widget(probe_block, area);
+
+    let [most_area, least_area] = inner_area.layout(&Layout::horizontal([Constraint::Fill(1), Constraint::Fill(1)]).spacing(2));
+    let token_width = distribution.iter().map(|(token, _)| token.chars().count()).max().unwrap_or(1);
+
+    // Each column fits as many tokens as there are rows, so a small vocabulary is split between them.
+    let rows = (most_area.height as usize).saturating_sub(1);
+    let most = distribution.len().div_ceil(2).min(rows);
+    let least = (distribution.len() - most).min(rows);
+
+    let ranked_lines = |title: &str, ranked: Vec<(usize, &(String, f32))>| -> Vec<Line> {
+        let mut lines = vec![Line::styled(title.to_string(), Style::default().fg(Color::Blue).bold())];
+        lines.extend(ranked.into_iter().map(|(rank, (token, probability))| {
+            Line::from(vec![
+                Span::raw(format!("{:>3} {:>token_width$} {:>7.4} ", rank + 1, token, probability)),
+                Span::styled(
+                    "█".repeat((probability * BAR_WIDTH as f32).round() as usize),
+                    Style::default().fg(Palette::TRAINING_LOSS_COLOR),
+                ),
+            ])
+        }));
+        lines
+    };
+
+    let most_lines = ranked_lines("most likely", distribution.iter().enumerate().take(most).collect());
+    let least_lines = ranked_lines("least likely", distribution.iter().enumerate().rev().take(least).collect());
+
+    frame.render_widget(Paragraph::new(most_lines), most_area);
+    frame.render_widget(Paragraph::new(least_lines), least_area);
+
+    area
+}
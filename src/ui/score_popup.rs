@@ -0,0 +1,85 @@
+use crate::{app::message::ScoreStep, ui::colors::Palette};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Draw the per-character probabilities of a scored word over the main screen, optionally with the context
+// window each prediction was conditioned on.
+pub fn draw(frame: &mut Frame, score: &Option<(String, Vec<ScoreStep>)>, show_context: bool) -> Rect {
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Percentage(60)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Percentage(40)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let (title, lines) = match score {
+        Some((word, steps)) => {
+            let log_probability: f32 = steps.iter().map(|step| step.probability.ln()).sum();
+            let context_width = steps
+                .iter()
+                .map(|step| step.context.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("context".len());
+            let token_width = steps
+                .iter()
+                .map(|step| step.token.chars().count())
+                .max()
+                .unwrap_or(0)
+                .max("next".len());
+
+            let mut lines: Vec<Line> = vec![Line::from(Span::styled(
+                format!(
+                    "{}{:>token_width$} {:>8} {:>8}",
+                    if show_context {
+                        format!("{:<width$} ", "context", width = context_width)
+                    } else {
+                        String::new()
+                    },
+                    "next",
+                    "prob",
+                    "log",
+                    token_width = token_width
+                ),
+                Style::default().fg(Color::Blue),
+            ))];
+            lines.extend(steps.iter().map(|step| {
+                let mut spans: Vec<Span> = Vec::new();
+                if show_context {
+                    spans.push(Span::styled(
+                        format!("{:<width$} ", step.context, width = context_width),
+                        Style::default().fg(Palette::BORDER_COLOR),
+                    ));
+                }
+                spans.push(Span::raw(format!(
+                    "{:>token_width$} {:>8.4} {:>8.3}",
+                    step.token,
+                    step.probability,
+                    step.probability.ln(),
+                    token_width = token_width
+                )));
+                Line::from(spans)
+            }));
+
+            (format!("Score: {} (log prob {:.3})", word, log_probability), lines)
+        }
+        None => ("Score".to_string(), vec![Line::from("waiting for the model...")]),
+    };
+
+    let score_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title(title);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(score_block), area);
+
+    area
+}
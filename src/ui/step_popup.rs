@@ -0,0 +1,72 @@
+use crate::{app::message::GenerationStep, ui::colors::Palette};
+
+use ratatui::{
+    Frame,
+    layout::{Constraint, Flex, Layout, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Bar, BarChart, Block, BorderType, Clear, Padding, Paragraph},
+};
+
+// Draw the distribution the next generated token is sampled from, along with the context window it was
+// conditioned on and the word so far.
+pub fn draw(frame: &mut Frame, step: &GenerationStep) -> Rect {
+    // Bars are as wide as the longest token so syllable labels fit.
+    let bar_width = step
+        .candidates
+        .iter()
+        .map(|(token, _)| token.chars().count())
+        .max()
+        .unwrap_or(1)
+        .max(1) as u16;
+
+    let area = frame.area();
+    let vertical = Layout::vertical([Constraint::Length(16)]).flex(Flex::Center);
+    let horizontal = Layout::horizontal([Constraint::Length(step.candidates.len() as u16 * (bar_width + 1) + 3)]).flex(Flex::Center);
+    let [area] = area.layout(&vertical);
+    let [area] = area.layout(&horizontal);
+
+    let step_block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .border_style(Palette::BORDER_COLOR)
+        .padding(Padding::horizontal(1))
+        .style((Palette::FG_COLOR, Palette::BG_COLOR))
+        .title("Next Token");
+
+    let inner_area = step_block.inner(area);
+    let [header_area, chart_area] = inner_area.layout(&Layout::vertical([Constraint::Length(2), Constraint::Fill(1)]));
+
+    let header = vec![
+        Line::from(vec![
+            Span::styled("context=", Style::default().fg(Color::Blue).bold()),
+            Span::raw(step.context.clone()),
+        ]),
+        Line::from(vec![
+            Span::styled("word=", Style::default().fg(Color::Blue).bold()),
+            Span::raw(step.output.clone()),
+        ]),
+    ];
+
+    // Bars are in percent, the most likely characters are highlighted.
+    let bars: Vec<Bar> = step
+        .candidates
+        .iter()
+        .map(|(token, probability)| {
+            let percent = (probability * 100.).round() as u64;
+            Bar::new(percent).label(token.clone()).text_value("").style(if *probability >= 0.1 {
+                Palette::TRAINING_LOSS_COLOR
+            } else {
+                Palette::VALIDATION_LOSS_COLOR
+            })
+        })
+        .collect();
+
+    let chart = BarChart::vertical(bars).bar_width(bar_width).bar_gap(1).max(100);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(step_block, area);
+    frame.render_widget(Paragraph::new(header), header_area);
+    frame.render_widget(chart, chart_area);
+
+    area
+}
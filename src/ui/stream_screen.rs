@@ -0,0 +1,39 @@
+use crate::ui::colors::{Palette, Rainbow};
+
+use ratatui::{
+    Frame,
+    layout::{Alignment, Rect},
+    style::Style,
+    text::Line,
+    widgets::{Block, Clear, Paragraph},
+};
+
+// The brightest row of the rainbow gradient.
+const STREAM_GRADIENT_ROW: usize = 5;
+
+// Fill the screen with the newest generated strings, scrolling up from the bottom. Each string keeps its
+// rainbow color as it scrolls so the bands move with the text.
+pub fn draw(frame: &mut Frame, generated: &[String]) -> Rect {
+    let area = frame.area();
+    let visible = generated.len().min(area.height as usize);
+    let first = generated.len() - visible;
+
+    let mut lines: Vec<Line> = vec![Line::from(""); area.height as usize - visible];
+    lines.extend(generated[first..].iter().enumerate().map(|(offset, text)| {
+        let rainbow = &Rainbow::ROYGBIV[(first + offset) % Rainbow::ROYGBIV.len()];
+        Line::styled(
+            text.clone(),
+            Style::default().fg(rainbow.gradient_color(STREAM_GRADIENT_ROW)).bold(),
+        )
+    }));
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(Block::new().style((Palette::FG_COLOR, Palette::BG_COLOR))),
+        area,
+    );
+
+    area
+}